@@ -2,34 +2,41 @@ use ai_functions::ai_function;
 
 #[ai_function]
 pub fn print_backend_webserver_code(_project_description_and_template: &str) {
-    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, CODE_TEMPLATE and PORT for a website backend build
     /// IMPORTANT: The backend code is ONLY an example. If the Project Description requires it, make as many changes as you like.
     /// IMPORTANT: You do not need to follow the backend code exactly. Write functions that make sense for the users request if required.
     /// FUNCTION: Takes an existing set of code marked as CODE_TEMPLATE and updates or re-writes it to work for the purpose in the PROJECT_DESCRIPTION
     /// IMPORTANT: The following libraries are already installed
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
     /// No other external libraries should be used. Write functions that fit with the description from the PROJECT_DESCRIPTION
+    /// IMPORTANT: If MIGRATIONS are given, data is stored in SQLite instead of a JSON file, using rusqlite, which is installed as well.
+    ///   The migrations are applied at startup with include_str!("../migrations/<name>") and queries use their tables and columns.
+    /// IMPORTANT: The server binds to 127.0.0.1 on the given PORT, whatever port the CODE_TEMPLATE uses.
     /// OUTPUT: IMPORTANT Print ONLY the code, nothing else. This function ONLY prints code!! do not add ```rust or ``` at the start or end!
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_improved_webserver_code(_project_description_and_template: &str) {
-    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, CODE_TEMPLATE and PORT for a website backend build
     /// FUNCTION: Performs the following tasks:
     ///   1. Removes any bugs in the code and adds minor additional functionality
     ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature. No code should be implemented later. Everything should be written now.
     ///   3. ONLY writes the code. No commentary.
     /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    ///   rusqlite as well if MIGRATIONS are given, which the code keeps storing its data with
+    /// IMPORTANT: The server keeps binding to 127.0.0.1 on the given PORT.
     /// OUTPUT: IMPORTANT Print ONLY the code, nothing else. This function ONLY prints code!! do not add ```rust or ``` at the start or end!
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_fixed_code(_broken_code_with_bugs: &str) {
-    /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found
+    /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found, and the MIGRATIONS its database uses if any
     /// FUNCTION: Removes bugs from code
+    /// IMPORTANT: Does not use ANY libraries other than reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    ///   and rusqlite if MIGRATIONS are given
     /// OUTPUT: IMPORTANT Print ONLY the code, nothing else. This function ONLY prints code!! do not add ```rust or ``` at the start or end!
     println!(OUTPUT)
 }
@@ -0,0 +1,47 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_data_model(_project_description: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION of a website backend that needs CRUD functionality
+    /// FUNCTION: Designs the entities the backend stores, their fields and the relations between them
+    /// IMPORTANT: Every entity has an integer primary key named id. Entity and field names are snake_case.
+    /// IMPORTANT: sql_type is one of INTEGER, TEXT, REAL or BLOB. Relations are foreign key fields whose
+    ///   references is "entity.field" of the entity they point to, otherwise references is null.
+    /// OUTPUT: Prints a JSON list of entities in the following format:
+    ///   [
+    ///     {
+    ///       "name": "user",
+    ///       "fields": [
+    ///         {"name": "id", "sql_type": "INTEGER", "is_primary_key": true, "is_nullable": false, "references": null},
+    ///         {"name": "username", "sql_type": "TEXT", "is_primary_key": false, "is_nullable": false, "references": null}
+    ///       ]
+    ///     },
+    ///     {
+    ///       "name": "task",
+    ///       "fields": [
+    ///         {"name": "id", "sql_type": "INTEGER", "is_primary_key": true, "is_nullable": false, "references": null},
+    ///         {"name": "user_id", "sql_type": "INTEGER", "is_primary_key": false, "is_nullable": false, "references": "user.id"},
+    ///         {"name": "completed", "sql_type": "INTEGER", "is_primary_key": false, "is_nullable": false, "references": null}
+    ///       ]
+    ///     }
+    ///   ]
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_sql_migrations(_data_model: &str) {
+    /// INPUT: Takes in a DATA_MODEL of entities, their fields and relations as JSON
+    /// FUNCTION: Writes the SQLite migration that creates a table for every entity
+    /// IMPORTANT: One CREATE TABLE per entity named after it, with a column per field.
+    ///   Fields with references become FOREIGN KEY constraints. Tables are created after the tables they reference.
+    /// OUTPUT: IMPORTANT Print ONLY the SQL, nothing else. This function ONLY prints SQL!! do not add ```sql or ``` at the start or end!
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_sql_migrations(_broken_sql_with_errors: &str) {
+    /// INPUT: Takes in a DATA_MODEL, the SQLite BROKEN_MIGRATION for it and the ERRORS found when applying it
+    /// FUNCTION: Fixes the migration so that it applies cleanly and creates every entity of the DATA_MODEL
+    /// OUTPUT: IMPORTANT Print ONLY the SQL, nothing else. This function ONLY prints SQL!! do not add ```sql or ``` at the start or end!
+    println!(OUTPUT)
+}
@@ -0,0 +1,24 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_readme(_project_description_and_endpoints: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, the API_BASE_URL of the web server, the ENV_VARS its code reads,
+    ///   its API_ENDPOINTS as JSON route objects and the PROJECT_FILES it ships with
+    /// FUNCTION: Writes the README.md of the web server project
+    /// IMPORTANT: Has these sections: what the project does, how to run it with cargo run, its environment variables,
+    ///   how to run its tests with cargo test, and a quick start with curl examples.
+    /// IMPORTANT: Documents every variable in ENV_VARS and no others.
+    /// IMPORTANT: curl examples only call routes listed in API_ENDPOINTS, with their method, at API_BASE_URL.
+    ///   Dynamic segments such as {id} are filled in with an example value, e.g. curl -X DELETE http://localhost:8080/item/1
+    ///   The full endpoint reference is in docs/API.md, which the README links to instead of repeating.
+    /// OUTPUT: IMPORTANT Print ONLY the markdown, nothing else. Do not wrap it in ```markdown or ``` at the start or end!
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_readme(_broken_readme_with_errors: &str) {
+    /// INPUT: Takes in the API_ENDPOINTS and ENV_VARS of a web server, its README and the ERRORS found in it
+    /// FUNCTION: Fixes the errors, so that curl examples only call routes in API_ENDPOINTS and every variable in ENV_VARS is documented
+    /// OUTPUT: IMPORTANT Print ONLY the markdown, nothing else. Do not wrap it in ```markdown or ``` at the start or end!
+    println!(OUTPUT)
+}
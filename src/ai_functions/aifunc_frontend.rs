@@ -0,0 +1,29 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_frontend_code(_project_description_and_endpoints: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, the API_BASE_URL of the backend and its API_ENDPOINTS as JSON route objects
+    /// FUNCTION: Writes a static website frontend for the PROJECT_DESCRIPTION that uses the backend
+    /// IMPORTANT: Plain HTML, JavaScript and CSS only. No frameworks, build steps or libraries from a CDN.
+    /// IMPORTANT: Every route in API_ENDPOINTS is called from the page with fetch, using its method and request_body.
+    ///   Calls no endpoint that is not listed in API_ENDPOINTS.
+    ///   Urls start with const API_BASE = API_BASE_URL, e.g. fetch(`${API_BASE}/item/${id}`, { method: "DELETE" })
+    ///   Dynamic segments such as {id} are filled in from the data on the page.
+    /// IMPORTANT: index.html loads styles.css and app.js from the same directory
+    /// OUTPUT: Prints exactly three code blocks and nothing else:
+    ///   ```html for index.html
+    ///   ```javascript for app.js
+    ///   ```css for styles.css
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_frontend_code(_broken_frontend_with_errors: &str) {
+    /// INPUT: Takes in the API_ENDPOINTS of a backend, a FRONTEND of index.html, app.js and styles.css and the ERRORS found in it
+    /// FUNCTION: Fixes the errors, so that the frontend only calls endpoints listed in API_ENDPOINTS, using their method
+    /// OUTPUT: Prints exactly three code blocks and nothing else:
+    ///   ```html for index.html
+    ///   ```javascript for app.js
+    ///   ```css for styles.css
+    println!(OUTPUT)
+}
@@ -13,3 +13,21 @@ pub fn convert_user_input_to_goal(_user_request: &str) {
     ///   OUTPUT = "build a website that fetches and stores crypto price data within a supabase setup including a frontend UI to fetch the data."
     println!(OUTPUT)
 }
+
+#[ai_function]
+pub fn summarize_conversation(_conversation: &str) {
+    /// Input: Takes in the earlier part of a CONVERSATION between an agent and an LLM, one message per line starting with [user] or [assistant]
+    /// Function: Summarizes what was asked, what was produced and which errors or decisions came up, so the agent can continue without the full transcript
+    /// Important: Keep names of routes, files, structs and functions, and any error messages that were not resolved
+    /// Output: Prints the summary as plain text. No commentary.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_repaired_json(_invalid_output: &str) {
+    /// Input: Takes in the EXPECTED_SCHEMA of some JSON, the ERROR a JSON parser reported and the INVALID_OUTPUT that failed to parse
+    /// Function: Rewrites INVALID_OUTPUT as valid JSON matching EXPECTED_SCHEMA, keeping all of its values
+    /// Important: Fixes the reported ERROR. Does not invent values that are not in INVALID_OUTPUT.
+    /// Output: Prints only the corrected JSON. No commentary and no code fences.
+    println!(OUTPUT)
+}
@@ -0,0 +1,23 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_integration_tests(_project_description_and_endpoints: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, the API_BASE_URL of a running web server and its API_ENDPOINTS as JSON route objects
+    /// FUNCTION: Writes a Rust integration test file, tests/api_tests.rs, that checks the web server does what the PROJECT_DESCRIPTION asks for
+    /// IMPORTANT: Tests call the running server at API_BASE_URL over HTTP. They cannot import anything from the server crate.
+    /// IMPORTANT: Covers every route in API_ENDPOINTS with its method, sending a request_body shaped like the one given.
+    ///   Checks status codes and the JSON returned. Dynamic segments such as {id} use ids created earlier in the same test.
+    ///   Each test creates the data it needs, so the tests pass in any order.
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY other libraries.
+    ///   reqwest, serde, serde_json, tokio
+    /// OUTPUT: IMPORTANT Print ONLY the code, nothing else. This function ONLY prints code!! do not add ```rust or ``` at the start or end!
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_integration_tests(_broken_tests_with_errors: &str) {
+    /// INPUT: Takes in the API_ENDPOINTS of a web server, Rust integration tests in BROKEN_TESTS and the ERRORS from cargo test
+    /// FUNCTION: Fixes the tests so that they compile, testing the same behaviour of the web server as before
+    /// OUTPUT: IMPORTANT Print ONLY the code, nothing else. This function ONLY prints code!! do not add ```rust or ``` at the start or end!
+    println!(OUTPUT)
+}
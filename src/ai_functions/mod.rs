@@ -1,3 +1,89 @@
 pub mod aifunc_architect;
 pub mod aifunc_backend;
+pub mod aifunc_database;
+pub mod aifunc_docs;
+pub mod aifunc_frontend;
 pub mod aifunc_managing;
+pub mod aifunc_qa;
+
+use aifunc_architect::{print_project_scope, print_site_urls};
+use aifunc_backend::{
+    print_backend_webserver_code, print_fixed_code, print_improved_webserver_code,
+    print_rest_api_endpoints,
+};
+use aifunc_database::{print_data_model, print_fixed_sql_migrations, print_sql_migrations};
+use aifunc_docs::{print_fixed_readme, print_readme};
+use aifunc_frontend::{print_fixed_frontend_code, print_frontend_code};
+use aifunc_managing::{convert_user_input_to_goal, print_repaired_json, summarize_conversation};
+use aifunc_qa::{print_fixed_integration_tests, print_integration_tests};
+
+// What #[ai_function] turns a function into: its own source as the prompt
+pub type AiFunction = for<'a> fn(&'a str) -> &'static str;
+
+// Every ai_function the agents call, named as get_function_string! names them
+pub const AI_FUNCTIONS: [(&str, AiFunction); 18] = [
+    (
+        get_function_string!(convert_user_input_to_goal),
+        convert_user_input_to_goal,
+    ),
+    (
+        get_function_string!(summarize_conversation),
+        summarize_conversation,
+    ),
+    (
+        get_function_string!(print_repaired_json),
+        print_repaired_json,
+    ),
+    (
+        get_function_string!(print_project_scope),
+        print_project_scope,
+    ),
+    (get_function_string!(print_site_urls), print_site_urls),
+    (
+        get_function_string!(print_backend_webserver_code),
+        print_backend_webserver_code,
+    ),
+    (
+        get_function_string!(print_improved_webserver_code),
+        print_improved_webserver_code,
+    ),
+    (get_function_string!(print_fixed_code), print_fixed_code),
+    (
+        get_function_string!(print_rest_api_endpoints),
+        print_rest_api_endpoints,
+    ),
+    (
+        get_function_string!(print_frontend_code),
+        print_frontend_code,
+    ),
+    (
+        get_function_string!(print_fixed_frontend_code),
+        print_fixed_frontend_code,
+    ),
+    (get_function_string!(print_data_model), print_data_model),
+    (
+        get_function_string!(print_sql_migrations),
+        print_sql_migrations,
+    ),
+    (
+        get_function_string!(print_fixed_sql_migrations),
+        print_fixed_sql_migrations,
+    ),
+    (
+        get_function_string!(print_integration_tests),
+        print_integration_tests,
+    ),
+    (
+        get_function_string!(print_fixed_integration_tests),
+        print_fixed_integration_tests,
+    ),
+    (get_function_string!(print_readme), print_readme),
+    (get_function_string!(print_fixed_readme), print_fixed_readme),
+];
+
+pub fn find_ai_function(name: &str) -> Option<AiFunction> {
+    AI_FUNCTIONS
+        .iter()
+        .find(|(ai_function, _)| *ai_function == name)
+        .map(|(_, ai_func)| *ai_func)
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{
+    llm_error::LlmError,
+    usage::{PriceTable, UsageLedger},
+};
+
+// Limits for a whole run, unset limits are not enforced
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Budget {
+    pub max_tokens: Option<u64>,
+    pub max_cost_usd: Option<f64>,
+    pub max_minutes: Option<f64>,
+    // Where the factsheet is saved when a limit stops the run
+    pub checkpoint_path: String,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            max_tokens: None,
+            max_cost_usd: None,
+            max_minutes: None,
+            checkpoint_path: "checkpoint.json".to_string(),
+        }
+    }
+}
+
+impl Budget {
+    // Called before every LLM request so an exhausted run makes no further calls
+    pub fn check(&self, ledger: &UsageLedger, prices: &PriceTable) -> Result<(), LlmError> {
+        if let Some(max_tokens) = self.max_tokens {
+            let used = ledger.total().total_tokens() as u64;
+            if used >= max_tokens {
+                return Err(LlmError::BudgetExceeded(format!(
+                    "used {} of {} tokens",
+                    used, max_tokens
+                )));
+            }
+        }
+
+        if let Some(max_cost_usd) = self.max_cost_usd {
+            let spent = ledger.report(prices).total_cost;
+            if spent >= max_cost_usd {
+                return Err(LlmError::BudgetExceeded(format!(
+                    "spent ${:.4} of ${:.2}",
+                    spent, max_cost_usd
+                )));
+            }
+        }
+
+        if let Some(max_minutes) = self.max_minutes {
+            let elapsed = ledger.elapsed();
+            if elapsed >= Duration::from_secs_f64(max_minutes.max(0.0) * 60.0) {
+                return Err(LlmError::BudgetExceeded(format!(
+                    "ran for {:.1} of {} minutes",
+                    elapsed.as_secs_f64() / 60.0,
+                    max_minutes
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::general::llm::Usage;
+
+    #[test]
+    fn tests_enforcing_token_and_cost_limits() {
+        let ledger = UsageLedger::default();
+        let prices = PriceTable::default();
+
+        let budget = Budget {
+            max_tokens: Some(2_000),
+            max_cost_usd: Some(1.0),
+            ..Budget::default()
+        };
+        assert!(budget.check(&ledger, &prices).is_ok());
+
+        let usage = Usage {
+            prompt_tokens: 1_500,
+            completion_tokens: 500,
+        };
+        ledger.record(
+            "Backend Developer",
+            "print_fixed_code",
+            "gpt-4-turbo",
+            usage,
+        );
+
+        let err = budget.check(&ledger, &prices).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Budget exhausted: used 2000 of 2000 tokens"
+        );
+
+        let cost_budget = Budget {
+            max_cost_usd: Some(0.02),
+            ..Budget::default()
+        };
+        assert!(matches!(
+            cost_budget.check(&ledger, &prices),
+            Err(LlmError::BudgetExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn tests_enforcing_wall_clock_limit() {
+        let budget = Budget {
+            max_minutes: Some(0.0),
+            ..Budget::default()
+        };
+
+        assert!(budget
+            .check(&UsageLedger::default(), &PriceTable::default())
+            .is_err());
+    }
+}
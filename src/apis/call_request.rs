@@ -1,77 +1,582 @@
-use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
-use dotenv::dotenv;
-use reqwest::Client;
-use std::env;
+use crate::models::general::llm::{
+    APIResponse, APIStreamChunk, AnthropicMessages, AnthropicResponse, AnthropicStreamEvent,
+    AnthropicUsage, ChatCompletion, ChatOptions, LlmResponse, Message, OllamaChat, OllamaOptions,
+    OllamaResponse, ResponseFormat, StreamOptions, Usage,
+};
+use reqwest::{Client, Response};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
 
 use reqwest::header::{HeaderMap, HeaderValue};
 
-// Call LLM GPT 4 turbo
-pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error + Send>> {
-    dotenv().ok();
+use super::{
+    llm_client::LlmProvider, llm_config::LlmConfig, llm_error::LlmError, session::LlmSession,
+};
 
-    // Extract API key
-    let api_key = env::var("OPEN_AI_KEY").expect("Key not found in enviroment");
-    let api_org = env::var("OPEN_AI_ORG").expect("Org not found in enviroment");
+// Receives each streamed token as it arrives
+pub type OnToken<'a> = dyn FnMut(&str) + Send + 'a;
 
-    let url = "https://api.openai.com/v1/chat/completions";
+// Build a client with the configured timeout and default headers
+fn build_client(config: &LlmConfig, headers: HeaderMap) -> Result<Client, LlmError> {
+    Client::builder()
+        .default_headers(headers)
+        .timeout(config.timeout())
+        .build()
+        .map_err(LlmError::Transport)
+}
+
+fn invalid_header(setting: &str) -> LlmError {
+    LlmError::ConfigInvalid(format!("{} is not a valid header value", setting))
+}
+
+// Fail on error statuses, keeping the body so the provider's message is not lost
+async fn check_status(response: Response) -> Result<Response, LlmError> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = parse_retry_after(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    Err(LlmError::from_status(status.as_u16(), body, retry_after))
+}
+
+// OpenAI sends retry-after-ms alongside the standard header in seconds
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value >= 0.0)
+    };
+
+    header_value("retry-after-ms")
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+        .or_else(|| header_value("retry-after").map(Duration::from_secs_f64))
+}
+
+// Decode a successful response body into the expected type
+async fn decode_response<T: DeserializeOwned>(response: Response) -> Result<T, LlmError> {
+    let body = check_status(response).await?.text().await?;
+
+    serde_json::from_str(&body).map_err(|source| LlmError::Decode { source, body })
+}
+
+// Per-request overrides win over the client's config
+fn model(config: &LlmConfig, options: &ChatOptions) -> String {
+    options
+        .model
+        .clone()
+        .unwrap_or_else(|| config.model.clone())
+}
+
+fn temperature(config: &LlmConfig, options: &ChatOptions) -> f32 {
+    options.temperature.unwrap_or(config.temperature)
+}
+
+fn max_tokens(config: &LlmConfig, options: &ChatOptions) -> u32 {
+    options.max_tokens.unwrap_or(config.max_tokens)
+}
 
+// Client carrying the OpenAI auth headers
+fn openai_client(config: &LlmConfig) -> Result<Client, LlmError> {
     // Create headers
     let mut headers = HeaderMap::new();
 
-    // Create API key header
-    headers.insert(
-        "Authorization",
-        HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-    // Create Open AI org header
-    headers.insert(
-        "OpenAI-Organization",
-        HeaderValue::from_str(api_org.as_str())
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-
-    // Create client
-    let client: Client = Client::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
+    // Create API key header, local stand-ins usually run without one
+    if let Some(api_key) = &config.api_key {
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|_| invalid_header("api_key"))?,
+        );
+    }
+
+    // Create Open AI org header, only needed for multi-org accounts
+    if let Some(api_org) = &config.organization {
+        headers.insert(
+            "OpenAI-Organization",
+            HeaderValue::from_str(api_org.as_str()).map_err(|_| invalid_header("organization"))?,
+        );
+    }
+
+    build_client(config, headers)
+}
+
+// Client carrying the Anthropic auth and version headers
+fn anthropic_client(config: &LlmConfig) -> Result<Client, LlmError> {
+    let mut headers = HeaderMap::new();
 
-    // Create chat completion
+    if let Some(api_key) = &config.api_key {
+        headers.insert(
+            "x-api-key",
+            HeaderValue::from_str(api_key).map_err(|_| invalid_header("api_key"))?,
+        );
+    }
+    headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
+
+    build_client(config, headers)
+}
+
+// Client with the provider's auth headers, built once per session so connections are reused
+pub fn http_client(config: &LlmConfig) -> Result<Client, LlmError> {
+    match config.provider {
+        LlmProvider::OpenAi => openai_client(config),
+        LlmProvider::Anthropic => anthropic_client(config),
+        LlmProvider::Ollama => build_client(config, HeaderMap::new()),
+    }
+}
+
+// Call an OpenAI compatible chat completions endpoint
+pub async fn call_gpt(
+    session: &LlmSession,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+) -> Result<LlmResponse, LlmError> {
+    let config = &session.config;
+    let url = config.endpoint("chat/completions");
+
+    // Create chat completion, max_tokens is only sent when set for this request
     let chat_completion = ChatCompletion {
-        model: "gpt-4-turbo".to_string(),
-        temperature: 0.1,
+        model: model(config, options),
+        temperature: temperature(config, options),
         messages,
+        stream: false,
+        stream_options: None,
+        max_tokens: options.max_tokens,
+        seed: options.seed,
+        stop: options.stop.clone(),
+        response_format: options.response_format.clone(),
+        tools: options.tools.clone(),
+        tool_choice: options.tool_choice.clone(),
     };
 
-    // Troubleshooting
-    // let response_raw = client
-    //     .post(url)
-    //     .json(&chat_completion)
-    //     .send()
-    //     .await
-    //     .unwrap();
-
-    // dbg!(response_raw.text().await.unwrap());
+    // Wait for room under the session's rate limits
+    let permit = session
+        .acquire(
+            &chat_completion.model,
+            &chat_completion.messages,
+            max_tokens(config, options),
+        )
+        .await;
 
     // Extract API response
-    let res: APIResponse = client
-        .post(url)
-        .json(&chat_completion)
-        .send()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?
-        .json()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
+    let response = session.http.post(url).json(&chat_completion).send().await?;
+    let res: APIResponse = decode_response(response).await?;
+
+    let usage = res.usage.unwrap_or_default();
+    permit.settle(usage);
 
     // Send response
-    Ok(res.choices[0].message.content.to_string())
+    res.choices
+        .into_iter()
+        .next()
+        .map(|choice| LlmResponse {
+            content: choice.message.content.unwrap_or_default(),
+            usage,
+            tool_calls: choice.message.tool_calls,
+        })
+        .ok_or(LlmError::EmptyChoices)
+}
+
+// Anthropic takes system prompts separately and needs at least one user message
+fn split_system_messages(messages: Vec<Message>) -> (Option<String>, Vec<Message>) {
+    let (system, chat): (Vec<Message>, Vec<Message>) =
+        messages.into_iter().partition(|msg| msg.role == "system");
+
+    // Function prompts are sent as a lone system message, so pass them as the user turn
+    if chat.is_empty() {
+        let user_messages = system
+            .into_iter()
+            .map(|msg| Message {
+                role: "user".to_string(),
+                content: msg.content,
+            })
+            .collect();
+
+        return (None, user_messages);
+    }
+
+    let system_prompt = system
+        .into_iter()
+        .map(|msg| msg.content)
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    if system_prompt.is_empty() {
+        (None, chat)
+    } else {
+        (Some(system_prompt), chat)
+    }
+}
+
+fn anthropic_usage(usage: AnthropicUsage) -> Usage {
+    Usage {
+        prompt_tokens: usage.input_tokens,
+        completion_tokens: usage.output_tokens,
+    }
+}
+
+// Call Anthropic Messages API
+pub async fn call_anthropic(
+    session: &LlmSession,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+) -> Result<LlmResponse, LlmError> {
+    let config = &session.config;
+    let url = config.endpoint("messages");
+
+    let (system, messages) = split_system_messages(messages);
+
+    // Create messages request
+    let request = AnthropicMessages {
+        model: model(config, options),
+        system,
+        messages,
+        max_tokens: max_tokens(config, options),
+        temperature: temperature(config, options),
+        stop_sequences: options.stop.clone(),
+        stream: false,
+    };
+
+    let permit = session
+        .acquire(&request.model, &request.messages, request.max_tokens)
+        .await;
+
+    // Extract API response
+    let response = session.http.post(url).json(&request).send().await?;
+    let res: AnthropicResponse = decode_response(response).await?;
+
+    let usage = res.usage.map(anthropic_usage).unwrap_or_default();
+    permit.settle(usage);
+
+    if res.content.is_empty() {
+        return Err(LlmError::EmptyChoices);
+    }
+
+    // Join text blocks into a single response
+    Ok(LlmResponse {
+        content: res
+            .content
+            .into_iter()
+            .map(|block| block.text)
+            .collect::<String>(),
+        usage,
+        tool_calls: vec![],
+    })
+}
+
+// Call a local Ollama server
+pub async fn call_ollama(
+    session: &LlmSession,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+) -> Result<LlmResponse, LlmError> {
+    let config = &session.config;
+    let url = config.endpoint("api/chat");
+
+    // Create chat request
+    let chat = OllamaChat {
+        model: model(config, options),
+        messages,
+        stream: false,
+        options: OllamaOptions {
+            temperature: temperature(config, options),
+            num_predict: options.max_tokens,
+            seed: options.seed,
+            stop: options.stop.clone(),
+        },
+        format: ollama_format(options),
+    };
+
+    let permit = session
+        .acquire(&chat.model, &chat.messages, max_tokens(config, options))
+        .await;
+
+    // Extract API response
+    let response = session.http.post(url).json(&chat).send().await?;
+    let res: OllamaResponse = decode_response(response).await?;
+
+    let usage = Usage {
+        prompt_tokens: res.prompt_eval_count,
+        completion_tokens: res.eval_count,
+    };
+    permit.settle(usage);
+
+    Ok(LlmResponse {
+        content: res.message.content.unwrap_or_default(),
+        usage,
+        tool_calls: vec![],
+    })
+}
+
+// Ollama has no tools, a requested tool's parameters become the reply schema instead
+fn ollama_format(options: &ChatOptions) -> Option<serde_json::Value> {
+    match &options.response_format {
+        Some(ResponseFormat::JsonSchema { json_schema }) => Some(json_schema.schema.clone()),
+        Some(ResponseFormat::JsonObject) => Some(serde_json::Value::from("json")),
+        Some(ResponseFormat::Text) => None,
+        None => options
+            .tools
+            .as_ref()
+            .and_then(|tools| tools.first())
+            .map(|tool| tool.function.parameters.clone()),
+    }
+}
+
+// Splits a text/event-stream body into the data payload of each event
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    // Feed raw bytes, returning payloads of every event completed so far
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut payloads = vec![];
+
+        // Events end on a blank line, bytes after it stay buffered for the next chunk
+        while let Some((end, delimiter_len)) = find_event_end(&self.buffer) {
+            let event: Vec<u8> = self.buffer.drain(..end + delimiter_len).collect();
+            let event = String::from_utf8_lossy(&event[..end]);
+
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|value| value.strip_prefix(' ').unwrap_or(value))
+                .collect::<Vec<&str>>();
+
+            if !data.is_empty() {
+                payloads.push(data.join("\n"));
+            }
+        }
+
+        payloads
+    }
+}
+
+fn find_event_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    let lf = buffer.windows(2).position(|window| window == b"\n\n");
+    let crlf = buffer.windows(4).position(|window| window == b"\r\n\r\n");
+
+    match (lf, crlf) {
+        (Some(lf), Some(crlf)) if crlf < lf => Some((crlf, 4)),
+        (Some(lf), _) => Some((lf, 2)),
+        (None, Some(crlf)) => Some((crlf, 4)),
+        (None, None) => None,
+    }
+}
+
+// Read an event stream, passing each event payload to handle_event until it returns false.
+// A stream that closes before that was cut off, so what arrived is not a whole reply.
+async fn read_event_stream(
+    mut response: reqwest::Response,
+    mut handle_event: impl FnMut(&str) -> Result<bool, LlmError>,
+) -> Result<(), LlmError> {
+    let mut parser = SseParser::default();
+
+    while let Some(chunk) = response.chunk().await? {
+        for payload in parser.push(&chunk) {
+            if !handle_event(&payload)? {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(LlmError::TruncatedStream)
+}
+
+// Anthropic reports failures after the 200 as an error event, given the status the
+// same error would have had before the stream started so retry can classify it
+fn anthropic_stream_error(payload: &str) -> LlmError {
+    let error_type = serde_json::from_str::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|event| event["error"]["type"].as_str().map(str::to_string));
+
+    let status = match error_type.as_deref() {
+        Some("invalid_request_error") => 400,
+        Some("authentication_error") => 401,
+        Some("permission_error") => 403,
+        Some("not_found_error") => 404,
+        Some("request_too_large") => 413,
+        Some("rate_limit_error") => 429,
+        Some("overloaded_error") => 529,
+        _ => 500,
+    };
+
+    LlmError::from_status(status, payload.to_string(), None)
+}
+
+fn decode_payload<T: DeserializeOwned>(payload: &str) -> Result<T, LlmError> {
+    serde_json::from_str(payload).map_err(|source| LlmError::Decode {
+        source,
+        body: payload.to_string(),
+    })
+}
+
+// Call an OpenAI compatible endpoint with stream enabled, forwarding tokens as they arrive
+pub async fn call_gpt_stream(
+    session: &LlmSession,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+    on_token: &mut OnToken<'_>,
+) -> Result<LlmResponse, LlmError> {
+    let config = &session.config;
+    let url = config.endpoint("chat/completions");
+
+    let chat_completion = ChatCompletion {
+        model: model(config, options),
+        temperature: temperature(config, options),
+        messages,
+        stream: true,
+        stream_options: Some(StreamOptions {
+            include_usage: true,
+        }),
+        max_tokens: options.max_tokens,
+        seed: options.seed,
+        stop: options.stop.clone(),
+        response_format: None,
+        tools: None,
+        tool_choice: None,
+    };
+
+    // The slot is held until the stream ends
+    let permit = session
+        .acquire(
+            &chat_completion.model,
+            &chat_completion.messages,
+            max_tokens(config, options),
+        )
+        .await;
+
+    let response = session.http.post(url).json(&chat_completion).send().await?;
+    let response = check_status(response).await?;
+
+    let mut content = String::new();
+    let mut usage = Usage::default();
+
+    read_event_stream(response, |payload| {
+        if payload == "[DONE]" {
+            return Ok(false);
+        }
+
+        let chunk: APIStreamChunk = decode_payload(payload)?;
+
+        if let Some(chunk_usage) = chunk.usage {
+            usage = chunk_usage;
+        }
+
+        if let Some(token) = chunk
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.delta.content)
+        {
+            on_token(&token);
+            content.push_str(&token);
+        }
+
+        Ok(true)
+    })
+    .await?;
+
+    permit.settle(usage);
+
+    if content.is_empty() {
+        return Err(LlmError::EmptyChoices);
+    }
+
+    Ok(LlmResponse {
+        content,
+        usage,
+        tool_calls: vec![],
+    })
+}
+
+// Call Anthropic Messages API with stream enabled, forwarding text deltas as they arrive
+pub async fn call_anthropic_stream(
+    session: &LlmSession,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+    on_token: &mut OnToken<'_>,
+) -> Result<LlmResponse, LlmError> {
+    let config = &session.config;
+    let url = config.endpoint("messages");
+
+    let (system, messages) = split_system_messages(messages);
+
+    let request = AnthropicMessages {
+        model: model(config, options),
+        system,
+        messages,
+        max_tokens: max_tokens(config, options),
+        temperature: temperature(config, options),
+        stop_sequences: options.stop.clone(),
+        stream: true,
+    };
+
+    let permit = session
+        .acquire(&request.model, &request.messages, request.max_tokens)
+        .await;
+
+    let response = session.http.post(url).json(&request).send().await?;
+    let response = check_status(response).await?;
+
+    let mut content = String::new();
+    let mut usage = Usage::default();
+
+    read_event_stream(response, |payload| {
+        let event: AnthropicStreamEvent = decode_payload(payload)?;
+
+        if let Some(input) = event.message.and_then(|message| message.usage) {
+            usage.prompt_tokens = input.input_tokens;
+        }
+        if let Some(output) = event.usage {
+            usage.completion_tokens = output.output_tokens;
+        }
+
+        match event.event_type.as_str() {
+            "content_block_delta" => {
+                if let Some(token) = event.delta.and_then(|delta| delta.text) {
+                    on_token(&token);
+                    content.push_str(&token);
+                }
+                Ok(true)
+            }
+            "message_stop" => Ok(false),
+            "error" => Err(anthropic_stream_error(payload)),
+            _ => Ok(true),
+        }
+    })
+    .await?;
+
+    permit.settle(usage);
+
+    if content.is_empty() {
+        return Err(LlmError::EmptyChoices);
+    }
+
+    Ok(LlmResponse {
+        content,
+        usage,
+        tool_calls: vec![],
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        apis::{
+            cassette::cassette_client,
+            retry::{classify, RetryClass},
+        },
+        models::general::llm::ToolChoice,
+    };
 
     #[tokio::test]
     async fn tests_call_to_openai() {
@@ -82,16 +587,119 @@ mod tests {
 
         let messages = vec![message];
 
-        let res: Result<String, Box<dyn std::error::Error + Send>> = call_gpt(messages).await;
+        // Replayed from a cassette, recorded through call_gpt
+        let llm = cassette_client("call_to_openai");
+
+        let res: Result<LlmResponse, LlmError> = llm.chat(messages, &ChatOptions::default()).await;
 
         match res {
             Ok(res_str) => {
                 dbg!(res_str);
-                assert!(true)
             }
-            Err(_) => {
-                assert!(false);
+            Err(e) => {
+                panic!("Call to OpenAI failed: {}", e);
             }
         }
     }
+
+    #[test]
+    fn tests_parsing_tool_calls_and_structured_requests() {
+        let body = r#"{"choices": [{"message": {"role": "assistant", "content": null,
+            "tool_calls": [{"id": "call_1", "type": "function", "function":
+            {"name": "print_project_scope", "arguments": "{\"is_crud_required\": true}"}}]}}]}"#;
+
+        let res: APIResponse = serde_json::from_str(body).unwrap();
+        let message = &res.choices[0].message;
+        assert_eq!(message.content, None);
+        assert_eq!(message.tool_calls[0].function.name, "print_project_scope");
+
+        let options = ChatOptions {
+            tool_choice: Some(ToolChoice::function("print_project_scope")),
+            ..ChatOptions::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&options).unwrap(),
+            serde_json::json!({"tool_choice": {"type": "function", "function": {"name": "print_project_scope"}}})
+        );
+
+        let json_mode = ChatOptions {
+            response_format: Some(ResponseFormat::JsonObject),
+            ..ChatOptions::default()
+        };
+        assert_eq!(ollama_format(&json_mode), Some("json".into()));
+    }
+
+    #[test]
+    fn tests_splitting_system_messages() {
+        let function_prompt = Message {
+            role: "system".to_string(),
+            content: "FUNCTION: print_site_urls".to_string(),
+        };
+
+        let (system, messages) = split_system_messages(vec![function_prompt.clone()]);
+        assert!(system.is_none());
+        assert_eq!(messages[0].role, "user");
+
+        let user_turn = Message {
+            role: "user".to_string(),
+            content: "Give me a short response".to_string(),
+        };
+
+        let (system, messages) = split_system_messages(vec![function_prompt, user_turn]);
+        assert_eq!(system, Some("FUNCTION: print_site_urls".to_string()));
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[test]
+    fn tests_parsing_retry_after_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert("retry-after", HeaderValue::from_static("20"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(20)));
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(
+            parse_retry_after(&headers),
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn tests_parsing_event_stream_chunks() {
+        let mut parser = SseParser::default();
+
+        // Events can be split anywhere, including inside a multi-byte character
+        let body =
+            "data: {\"a\":\"é\"}\n\n: keep-alive\n\nevent: done\r\ndata: [DONE]\r\n\r\n".as_bytes();
+        let split_at = body.iter().position(|&b| b == 0xC3).unwrap() + 1;
+
+        let mut payloads = parser.push(&body[..split_at]);
+        assert!(payloads.is_empty());
+
+        payloads.extend(parser.push(&body[split_at..]));
+        assert_eq!(payloads, vec!["{\"a\":\"é\"}", "[DONE]"]);
+    }
+
+    #[test]
+    fn tests_classifying_stream_errors() {
+        let overloaded = anthropic_stream_error(
+            r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#,
+        );
+        assert_eq!(
+            overloaded.to_string(),
+            "LLM API returned 529 (overloaded_error): Overloaded"
+        );
+        assert_eq!(classify(&overloaded), Some(RetryClass::ServerError));
+
+        let invalid = anthropic_stream_error(
+            r#"{"type": "error", "error": {"type": "invalid_request_error", "message": "Bad"}}"#,
+        );
+        assert_eq!(classify(&invalid), None);
+
+        assert_eq!(
+            classify(&LlmError::TruncatedStream),
+            Some(RetryClass::Transport)
+        );
+    }
 }
@@ -0,0 +1,402 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+use crate::models::general::llm::{ChatOptions, LlmResponse, Message};
+
+use super::{
+    call_request::OnToken, llm_client::LlmClient, llm_config::LlmConfig, llm_error::LlmError,
+    transcript::Transcript, usage::UsageLedger,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    // Talk to the provider directly
+    Off,
+    // Talk to the provider and write every exchange to the cassette
+    Record,
+    // Serve the cassette back without touching the network
+    Replay,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CassetteConfig {
+    pub mode: CassetteMode,
+    pub path: PathBuf,
+}
+
+impl Default for CassetteConfig {
+    fn default() -> Self {
+        Self {
+            mode: CassetteMode::Off,
+            path: PathBuf::from("cassettes/run.json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    messages: Vec<Message>,
+    response: LlmResponse,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+// Wraps a provider client to record its exchanges, or stands in for one on replay
+#[derive(Debug)]
+pub struct CassetteClient {
+    config: LlmConfig,
+    usage: UsageLedger,
+    transcript: Transcript,
+    // The provider being recorded, None when replaying
+    recorder: Option<Arc<dyn LlmClient>>,
+    cassette: Mutex<Cassette>,
+    // Index of the next interaction to replay
+    position: Mutex<usize>,
+}
+
+impl CassetteClient {
+    // Start an empty cassette, overwriting any earlier recording at the same path
+    pub fn record(config: LlmConfig, recorder: Arc<dyn LlmClient>) -> Result<Self, LlmError> {
+        Ok(Self {
+            usage: UsageLedger::default(),
+            transcript: Transcript::new(&config)?,
+            config,
+            recorder: Some(recorder),
+            cassette: Mutex::new(Cassette::default()),
+            position: Mutex::new(0),
+        })
+    }
+
+    pub fn replay(config: LlmConfig) -> Result<Self, LlmError> {
+        let path = &config.cassette.path;
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| LlmError::Cassette(format!("failed to read {}: {}", path.display(), e)))?;
+        let cassette = serde_json::from_str(&contents).map_err(|e| {
+            LlmError::Cassette(format!("failed to parse {}: {}", path.display(), e))
+        })?;
+
+        Ok(Self {
+            usage: UsageLedger::default(),
+            transcript: Transcript::new(&config)?,
+            config,
+            recorder: None,
+            cassette: Mutex::new(cassette),
+            position: Mutex::new(0),
+        })
+    }
+
+    // Interactions are served in the order they were recorded
+    fn replay_next(&self, messages: &[Message]) -> Result<LlmResponse, LlmError> {
+        let cassette = self.cassette.lock().unwrap();
+        let mut position = self.position.lock().unwrap();
+
+        let Some(interaction) = cassette.interactions.get(*position) else {
+            return Err(LlmError::Cassette(format!(
+                "all {} recorded interactions in {} were used, unexpected request:\n{}",
+                cassette.interactions.len(),
+                self.config.cassette.path.display(),
+                render_prompt(messages)
+            )));
+        };
+
+        if interaction.messages != messages {
+            return Err(LlmError::Cassette(format!(
+                "request {} does not match {}:\n{}",
+                *position + 1,
+                self.config.cassette.path.display(),
+                line_diff(
+                    &render_prompt(&interaction.messages),
+                    &render_prompt(messages)
+                )
+            )));
+        }
+
+        *position += 1;
+        Ok(interaction.response.clone())
+    }
+
+    // The whole cassette is rewritten each time so an aborted run keeps what it recorded
+    fn save(&self, messages: Vec<Message>, response: &LlmResponse) -> Result<(), LlmError> {
+        let path = &self.config.cassette.path;
+        let mut cassette = self.cassette.lock().unwrap();
+
+        cassette.interactions.push(Interaction {
+            messages,
+            response: response.clone(),
+        });
+
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, serde_json::to_string_pretty(&*cassette)?)
+        };
+
+        write()
+            .map_err(|e| LlmError::Cassette(format!("failed to write {}: {}", path.display(), e)))
+    }
+}
+
+#[async_trait]
+impl LlmClient for CassetteClient {
+    fn config(&self) -> &LlmConfig {
+        &self.config
+    }
+
+    fn usage(&self) -> &UsageLedger {
+        &self.usage
+    }
+
+    fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+    ) -> Result<LlmResponse, LlmError> {
+        match &self.recorder {
+            Some(recorder) => {
+                let response = recorder.chat(messages.clone(), options).await?;
+                self.save(messages, &response)?;
+                Ok(response)
+            }
+            None => self.replay_next(&messages),
+        }
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+        on_token: &mut OnToken<'_>,
+    ) -> Result<LlmResponse, LlmError> {
+        match &self.recorder {
+            Some(recorder) => {
+                let response = recorder
+                    .chat_stream(messages.clone(), options, on_token)
+                    .await?;
+                self.save(messages, &response)?;
+                Ok(response)
+            }
+            None => {
+                let response = self.replay_next(&messages)?;
+                on_token(&response.content);
+                Ok(response)
+            }
+        }
+    }
+}
+
+fn render_prompt(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|message| format!("[{}] {}", message.role, message.content))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Line diff of two prompts, showing changed lines with a little context around them
+fn line_diff(recorded: &str, requested: &str) -> String {
+    const CONTEXT: usize = 2;
+
+    let old: Vec<&str> = recorded.lines().collect();
+    let new: Vec<&str> = requested.lines().collect();
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<(char, &str)> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (tag, _))| *tag != ' ')
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut diff = String::from("--- recorded\n+++ requested\n");
+    let mut last_shown: Option<usize> = None;
+
+    for (index, (tag, line)) in lines.iter().enumerate() {
+        if !changed.iter().any(|&c| c.abs_diff(index) <= CONTEXT) {
+            continue;
+        }
+        if last_shown.is_some_and(|last| index > last + 1) {
+            diff.push_str("...\n");
+        }
+        diff.push_str(&format!("{} {}\n", tag, line));
+        last_shown = Some(index);
+    }
+
+    diff
+}
+
+// Client for tests: replays cassettes/<name>.json, or re-records it against the
+// configured provider when run with LLM_CASSETTE=record
+#[cfg(test)]
+pub fn cassette_client(name: &str) -> Arc<dyn LlmClient> {
+    use super::llm_client::{create_llm_client, LlmProvider};
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("cassettes")
+        .join(format!("{}.json", name));
+
+    let record = std::env::var("LLM_CASSETTE").is_ok_and(|mode| mode == "record");
+
+    let mut config = if record {
+        LlmConfig::load().expect("Failed to load LLM config")
+    } else {
+        LlmConfig::for_provider(LlmProvider::OpenAi)
+    };
+
+    config.cassette = CassetteConfig {
+        mode: if record {
+            CassetteMode::Record
+        } else {
+            CassetteMode::Replay
+        },
+        path,
+    };
+
+    create_llm_client(config).expect("Failed to create cassette client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::general::llm::Usage;
+
+    #[derive(Debug)]
+    struct EchoClient {
+        config: LlmConfig,
+        usage: UsageLedger,
+        transcript: Transcript,
+    }
+
+    #[async_trait]
+    impl LlmClient for EchoClient {
+        fn config(&self) -> &LlmConfig {
+            &self.config
+        }
+
+        fn usage(&self) -> &UsageLedger {
+            &self.usage
+        }
+
+        fn transcript(&self) -> &Transcript {
+            &self.transcript
+        }
+
+        async fn chat(
+            &self,
+            messages: Vec<Message>,
+            _options: &ChatOptions,
+        ) -> Result<LlmResponse, LlmError> {
+            Ok(LlmResponse {
+                content: format!("echo: {}", messages[0].content),
+                usage: Usage::default(),
+                tool_calls: vec![],
+            })
+        }
+    }
+
+    fn user_message(content: &str) -> Message {
+        Message {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_recording_and_replaying() {
+        let path = std::env::temp_dir().join(format!("cassette_test_{}.json", std::process::id()));
+
+        let mut config = LlmConfig::for_provider(crate::apis::llm_client::LlmProvider::Ollama);
+        config.cassette = CassetteConfig {
+            mode: CassetteMode::Record,
+            path: path.clone(),
+        };
+
+        let echo = Arc::new(EchoClient {
+            config: config.clone(),
+            usage: UsageLedger::default(),
+            transcript: Transcript::new(&config).unwrap(),
+        });
+        let recorder = CassetteClient::record(config.clone(), echo).unwrap();
+        recorder
+            .chat(vec![user_message("first")], &ChatOptions::default())
+            .await
+            .unwrap();
+        recorder
+            .chat(vec![user_message("second")], &ChatOptions::default())
+            .await
+            .unwrap();
+
+        let player = CassetteClient::replay(config).unwrap();
+        let first = player
+            .chat(vec![user_message("first")], &ChatOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(first.content, "echo: first");
+
+        let err = player
+            .chat(vec![user_message("third")], &ChatOptions::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("request 2 does not match"));
+        assert!(err.contains("- [user] second\n+ [user] third"));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tests_diffing_prompts_with_context() {
+        let recorded = "FUNCTION: print_project_scope\na\nb\nc\nd\ne\nf\nINPUT: fitness tracker";
+        let requested = "FUNCTION: print_project_scope\na\nb\nc\nd\ne\nf\nINPUT: forex prices";
+
+        assert_eq!(
+            line_diff(recorded, requested),
+            "--- recorded\n+++ requested\n  e\n  f\n- INPUT: fitness tracker\n+ INPUT: forex prices\n"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use tiktoken_rs::{
+    cl100k_base, o200k_base,
+    tokenizer::{get_tokenizer, Tokenizer},
+    CoreBPE,
+};
+
+use crate::models::general::llm::{ChatOptions, Message};
+
+use super::{llm_client::LlmProvider, llm_config::LlmConfig, llm_error::LlmError};
+
+// Role and separator tokens the chat format adds to every message
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    // Leave the oldest exchanges out of the prompt
+    DropOldest,
+    // Replace the oldest exchanges with a summary written by the LLM
+    Summarize,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContextConfig {
+    // Context window of the model in tokens, None uses the provider default
+    pub limit: Option<usize>,
+    pub strategy: ContextStrategy,
+    // Never drop or summarize system messages
+    pub pin_system: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            limit: None,
+            strategy: ContextStrategy::DropOldest,
+            pin_system: true,
+        }
+    }
+}
+
+// Messages that fit the window and the ones left out to make them fit
+#[derive(Debug, Default, PartialEq)]
+pub struct TrimmedMessages {
+    pub kept: Vec<Message>,
+    pub dropped: Vec<Message>,
+}
+
+// Token budget for one request: the context window minus room for the completion.
+// A model or max_tokens set for the request takes the place of the config's.
+#[derive(Debug, Clone)]
+pub struct ContextWindow {
+    model: String,
+    available: usize,
+    pin_system: bool,
+}
+
+impl ContextWindow {
+    pub fn new(config: &LlmConfig, options: &ChatOptions) -> Self {
+        let limit = config.context.limit.unwrap_or(match config.provider {
+            LlmProvider::OpenAi => 128_000,
+            LlmProvider::Anthropic => 200_000,
+            LlmProvider::Ollama => 8_192,
+        });
+
+        let max_tokens = options.max_tokens.unwrap_or(config.max_tokens);
+
+        Self {
+            model: options
+                .model
+                .clone()
+                .unwrap_or_else(|| config.model.clone()),
+            available: limit.saturating_sub(max_tokens as usize),
+            pin_system: config.context.pin_system,
+        }
+    }
+
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    pub fn count(&self, messages: &[Message]) -> usize {
+        messages
+            .iter()
+            .map(|message| count_tokens(&self.model, &message.content) + MESSAGE_OVERHEAD_TOKENS)
+            .sum()
+    }
+
+    // Drop the oldest unpinned messages until the prompt fits. The last message is the
+    // current request and is always kept; exchanges are dropped whole so the
+    // remaining history still starts with a user turn.
+    pub fn trim(&self, messages: Vec<Message>) -> Result<TrimmedMessages, LlmError> {
+        let mut total = self.count(&messages);
+        let mut keep = vec![true; messages.len()];
+        let last = messages.len().saturating_sub(1);
+
+        let droppable = |index: usize, keep: &[bool]| {
+            index < last && keep[index] && !(self.pin_system && messages[index].role == "system")
+        };
+
+        while total > self.available {
+            let Some(oldest) = (0..last).find(|&i| droppable(i, &keep)) else {
+                return Err(LlmError::ContextOverflow {
+                    tokens: total,
+                    limit: self.available,
+                });
+            };
+
+            keep[oldest] = false;
+            total -= self.count(&messages[oldest..=oldest]);
+
+            // Take the reply along with its prompt
+            if let Some(next) = (oldest + 1..last).find(|&i| droppable(i, &keep)) {
+                if messages[next].role == "assistant" {
+                    keep[next] = false;
+                    total -= self.count(&messages[next..=next]);
+                }
+            }
+        }
+
+        let mut trimmed = TrimmedMessages::default();
+        for (message, keep) in messages.into_iter().zip(keep) {
+            if keep {
+                trimmed.kept.push(message);
+            } else {
+                trimmed.dropped.push(message);
+            }
+        }
+
+        Ok(trimmed)
+    }
+
+    // Cut text down to at most max_tokens, e.g. a transcript that is too long to summarize
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let bpe = encoding_for(&self.model);
+
+        let tokens = bpe.encode_with_special_tokens(text);
+        if tokens.len() <= max_tokens {
+            return text.to_string();
+        }
+
+        // The cut can land inside a multi-byte character, so start from the next whole one
+        let tail = bpe._decode_native(&tokens[tokens.len() - max_tokens..]);
+        let start = tail
+            .iter()
+            .position(|byte| byte & 0b1100_0000 != 0b1000_0000)
+            .unwrap_or(tail.len());
+        let truncated = String::from_utf8_lossy(&tail[start..]).into_owned();
+
+        // Fewer tokens than the last character takes, keep that character anyway
+        match truncated.is_empty() {
+            true => text.chars().last().map(String::from).unwrap_or_default(),
+            false => truncated,
+        }
+    }
+}
+
+// Exact for OpenAI models, a close estimate for everything else
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    encoding_for(model).encode_with_special_tokens(text).len()
+}
+
+// Encodings are slow to build, so each is built once and shared
+fn encoding_for(model: &str) -> &'static CoreBPE {
+    static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+    static O200K: OnceLock<CoreBPE> = OnceLock::new();
+
+    match get_tokenizer(model) {
+        Some(Tokenizer::O200kBase) => {
+            O200K.get_or_init(|| o200k_base().expect("Bundled o200k encoding is valid"))
+        }
+        _ => CL100K.get_or_init(|| cl100k_base().expect("Bundled cl100k encoding is valid")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn window(available: usize) -> ContextWindow {
+        ContextWindow {
+            model: "gpt-4-turbo".to_string(),
+            available,
+            pin_system: true,
+        }
+    }
+
+    #[test]
+    fn tests_counting_tokens() {
+        assert_eq!(count_tokens("gpt-4-turbo", "hello world"), 2);
+        assert_eq!(window(100).count(&[message("user", "hello world")]), 6);
+    }
+
+    #[test]
+    fn tests_dropping_oldest_exchange_and_pinning_system() {
+        let messages = vec![
+            message("system", "You write Rust web servers"),
+            message("user", "first attempt please"),
+            message("assistant", "fn main() { broken }"),
+            message("user", "second attempt please"),
+            message("assistant", "fn main() {}"),
+            message("user", "fix the remaining bugs"),
+        ];
+
+        let full = window(1_000).count(&messages);
+        let trimmed = window(full - 1).trim(messages.clone()).unwrap();
+
+        assert_eq!(trimmed.dropped, messages[1..3].to_vec());
+        assert_eq!(trimmed.kept[0].role, "system");
+        assert_eq!(trimmed.kept[1].content, "second attempt please");
+        assert_eq!(trimmed.kept.len(), 4);
+    }
+
+    #[test]
+    fn tests_overflowing_with_current_prompt_alone() {
+        let messages = vec![message("user", "CODE TEMPLATE: fn main() {}")];
+
+        assert!(matches!(
+            window(3).trim(messages),
+            Err(LlmError::ContextOverflow { limit: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn tests_truncating_keeps_the_end() {
+        let truncated = window(100).truncate("one two three four", 2);
+
+        assert_eq!(truncated, " three four");
+    }
+
+    #[test]
+    fn tests_truncating_inside_a_character() {
+        let text = "Krabben 🦀🦀 schwimmen über Größen hinweg";
+        let window = window(100);
+
+        for max_tokens in 1..count_tokens("gpt-4-turbo", text) {
+            let truncated = window.truncate(text, max_tokens);
+
+            assert!(!truncated.is_empty());
+            assert!(text.ends_with(&truncated), "{:?}", truncated);
+        }
+        assert_eq!(window.truncate("🦀", 1), "🦀");
+    }
+}
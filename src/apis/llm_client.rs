@@ -0,0 +1,207 @@
+use std::{fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use strum_macros::{Display, EnumString};
+
+use crate::models::general::llm::{ChatOptions, LlmResponse, Message};
+
+use super::{
+    call_request::{
+        call_anthropic, call_anthropic_stream, call_gpt, call_gpt_stream, call_ollama, OnToken,
+    },
+    cassette::{CassetteClient, CassetteMode},
+    llm_config::LlmConfig,
+    llm_error::LlmError,
+    session::LlmSession,
+    transcript::Transcript,
+    usage::UsageLedger,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProvider {
+    OpenAi,
+    Anthropic,
+    Ollama,
+}
+
+#[async_trait]
+pub trait LlmClient: Debug + Send + Sync {
+    fn config(&self) -> &LlmConfig;
+
+    // Token usage of every completion made through this client
+    fn usage(&self) -> &UsageLedger;
+
+    // Where every exchange made through this client is logged
+    fn transcript(&self) -> &Transcript;
+
+    // Send messages with per-request settings and return the first completion
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+    ) -> Result<LlmResponse, LlmError>;
+
+    // Stream the completion through on_token and return the assembled content
+    // Providers without streaming support hand over the whole completion as one token
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+        on_token: &mut OnToken<'_>,
+    ) -> Result<LlmResponse, LlmError> {
+        let response = self.chat(messages, options).await?;
+        on_token(&response.content);
+        Ok(response)
+    }
+}
+
+#[derive(Debug)]
+pub struct OpenAiClient {
+    session: LlmSession,
+}
+
+#[async_trait]
+impl LlmClient for OpenAiClient {
+    fn config(&self) -> &LlmConfig {
+        &self.session.config
+    }
+
+    fn usage(&self) -> &UsageLedger {
+        &self.session.usage
+    }
+
+    fn transcript(&self) -> &Transcript {
+        &self.session.transcript
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+    ) -> Result<LlmResponse, LlmError> {
+        call_gpt(&self.session, messages, options).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+        on_token: &mut OnToken<'_>,
+    ) -> Result<LlmResponse, LlmError> {
+        call_gpt_stream(&self.session, messages, options, on_token).await
+    }
+}
+
+#[derive(Debug)]
+pub struct AnthropicClient {
+    session: LlmSession,
+}
+
+#[async_trait]
+impl LlmClient for AnthropicClient {
+    fn config(&self) -> &LlmConfig {
+        &self.session.config
+    }
+
+    fn usage(&self) -> &UsageLedger {
+        &self.session.usage
+    }
+
+    fn transcript(&self) -> &Transcript {
+        &self.session.transcript
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+    ) -> Result<LlmResponse, LlmError> {
+        call_anthropic(&self.session, messages, options).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+        on_token: &mut OnToken<'_>,
+    ) -> Result<LlmResponse, LlmError> {
+        call_anthropic_stream(&self.session, messages, options, on_token).await
+    }
+}
+
+#[derive(Debug)]
+pub struct OllamaClient {
+    session: LlmSession,
+}
+
+#[async_trait]
+impl LlmClient for OllamaClient {
+    fn config(&self) -> &LlmConfig {
+        &self.session.config
+    }
+
+    fn usage(&self) -> &UsageLedger {
+        &self.session.usage
+    }
+
+    fn transcript(&self) -> &Transcript {
+        &self.session.transcript
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        options: &ChatOptions,
+    ) -> Result<LlmResponse, LlmError> {
+        call_ollama(&self.session, messages, options).await
+    }
+}
+
+// Build the client for the configured provider, wrapped in a cassette when recording or replaying.
+// Create it once and share it: it owns the session with the pooled connections and rate limits.
+pub fn create_llm_client(config: LlmConfig) -> Result<Arc<dyn LlmClient>, LlmError> {
+    match config.cassette.mode {
+        CassetteMode::Off => create_provider_client(config),
+        CassetteMode::Record => Ok(Arc::new(CassetteClient::record(
+            config.clone(),
+            create_provider_client(config)?,
+        )?)),
+        CassetteMode::Replay => Ok(Arc::new(CassetteClient::replay(config)?)),
+    }
+}
+
+fn create_provider_client(config: LlmConfig) -> Result<Arc<dyn LlmClient>, LlmError> {
+    let provider = config.provider;
+    let session = LlmSession::new(config)?;
+
+    Ok(match provider {
+        LlmProvider::OpenAi => Arc::new(OpenAiClient { session }),
+        LlmProvider::Anthropic => Arc::new(AnthropicClient { session }),
+        LlmProvider::Ollama => Arc::new(OllamaClient { session }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parsing_provider_names() {
+        assert_eq!(
+            "openai".parse::<LlmProvider>().unwrap(),
+            LlmProvider::OpenAi
+        );
+        assert_eq!(
+            "Anthropic".parse::<LlmProvider>().unwrap(),
+            LlmProvider::Anthropic
+        );
+        assert_eq!(
+            "OLLAMA".parse::<LlmProvider>().unwrap(),
+            LlmProvider::Ollama
+        );
+        assert!("gemini".parse::<LlmProvider>().is_err());
+    }
+}
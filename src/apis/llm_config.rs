@@ -0,0 +1,463 @@
+use std::{collections::HashMap, env, fs, path::Path, str::FromStr, time::Duration};
+
+use dotenv::dotenv;
+use serde::Deserialize;
+
+use super::{
+    budget::Budget,
+    cassette::{CassetteConfig, CassetteMode},
+    context::ContextConfig,
+    llm_client::LlmProvider,
+    llm_error::LlmError,
+    prompts::PromptRegistry,
+    response_cache::ResponseCache,
+    retry::RetryPolicy,
+    routing::RoutingTable,
+    session::RateLimit,
+    structured_output::StructuredOutput,
+    transcript::TranscriptConfig,
+    usage::{ModelPrice, PriceTable},
+};
+
+// Used when LLM_CONFIG is not set
+const DEFAULT_CONFIG_PATH: &str = "llm.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmConfig {
+    pub provider: LlmProvider,
+    pub base_url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    pub organization: Option<String>,
+    pub timeout_secs: u64,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub stream: bool,
+    // How decoded ai_functions ask the provider for JSON
+    pub structured_output: StructuredOutput,
+    // Times the LLM is asked to fix output that could not be decoded
+    pub decode_retries: u32,
+    pub retry: RetryPolicy,
+    pub prices: PriceTable,
+    pub budget: Budget,
+    pub cache: ResponseCache,
+    pub cassette: CassetteConfig,
+    pub context: ContextConfig,
+    // Per ai_function model and sampling settings
+    pub routes: RoutingTable,
+    // Shared by all agents, enforced by the session
+    pub rate_limit: RateLimit,
+    // Templates that override the built-in ai_function prompts
+    pub prompts: PromptRegistry,
+    // JSONL log of every exchange, for troubleshooting
+    pub transcript: TranscriptConfig,
+}
+
+// Every field is optional so a file only needs to list what it changes
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LlmConfigFile {
+    provider: Option<LlmProvider>,
+    base_url: Option<String>,
+    model: Option<String>,
+    api_key: Option<String>,
+    organization: Option<String>,
+    timeout_secs: Option<u64>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    stream: Option<bool>,
+    structured_output: Option<StructuredOutput>,
+    decode_retries: Option<u32>,
+    retry: Option<RetryPolicy>,
+    prices: Option<HashMap<String, ModelPrice>>,
+    budget: Option<Budget>,
+    cache: Option<ResponseCache>,
+    cassette: Option<CassetteConfig>,
+    context: Option<ContextConfig>,
+    routes: Option<RoutingTable>,
+    rate_limit: Option<RateLimit>,
+    prompts: Option<PromptRegistry>,
+    transcript: Option<TranscriptConfig>,
+}
+
+impl LlmConfig {
+    // Defaults that work against each provider's public endpoint
+    pub fn for_provider(provider: LlmProvider) -> Self {
+        let (base_url, model) = match provider {
+            LlmProvider::OpenAi => ("https://api.openai.com/v1", "gpt-4-turbo"),
+            LlmProvider::Anthropic => ("https://api.anthropic.com/v1", "claude-3-opus-20240229"),
+            LlmProvider::Ollama => ("http://localhost:11434", "llama3"),
+        };
+
+        Self {
+            provider,
+            base_url: base_url.to_string(),
+            model: model.to_string(),
+            api_key: None,
+            organization: None,
+            timeout_secs: 120,
+            temperature: 0.1,
+            max_tokens: 4096,
+            stream: false,
+            structured_output: StructuredOutput::Off,
+            decode_retries: 2,
+            retry: RetryPolicy::default(),
+            prices: PriceTable::default(),
+            budget: Budget::default(),
+            cache: ResponseCache::default(),
+            cassette: CassetteConfig::default(),
+            context: ContextConfig::default(),
+            routes: RoutingTable::default(),
+            rate_limit: RateLimit::default(),
+            prompts: PromptRegistry::default(),
+            transcript: TranscriptConfig::default(),
+        }
+    }
+
+    // Load from the config file (if any) and then apply environment overrides
+    pub fn load() -> Result<Self, LlmError> {
+        let config = Self::read()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    // Same as load without requiring an API key, for commands that never call the LLM
+    pub fn read() -> Result<Self, LlmError> {
+        dotenv().ok();
+
+        let path = env::var("LLM_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+
+        let file = if Path::new(&path).exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| LlmError::ConfigInvalid(format!("{}: {}", path, e)))?;
+            Self::parse_file(&contents)?
+        } else if env::var("LLM_CONFIG").is_ok() {
+            return Err(LlmError::ConfigMissing(format!("config file {}", path)));
+        } else {
+            LlmConfigFile::default()
+        };
+
+        let provider = match env_value::<LlmProvider>("LLM_PROVIDER")? {
+            Some(provider) => provider,
+            None => file.provider.unwrap_or(LlmProvider::OpenAi),
+        };
+
+        let mut config = Self::for_provider(provider);
+        config.apply_file(file);
+        config.apply_env()?;
+        config.prompts.load()?;
+
+        Ok(config)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    // Join a path onto the base url without doubling slashes
+    pub fn endpoint(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    fn parse_file(contents: &str) -> Result<LlmConfigFile, LlmError> {
+        toml::from_str(contents).map_err(|e| LlmError::ConfigInvalid(e.to_string()))
+    }
+
+    // Hosted APIs always need a key, local servers, proxies and replays may not
+    fn validate(&self) -> Result<(), LlmError> {
+        if self.api_key.is_some() || self.cassette.mode == CassetteMode::Replay {
+            return Ok(());
+        }
+
+        match self.provider {
+            LlmProvider::OpenAi if self.base_url.starts_with("https://api.openai.com") => {
+                Err(LlmError::ConfigMissing("OPEN_AI_KEY".to_string()))
+            }
+            LlmProvider::Anthropic => Err(LlmError::ConfigMissing("ANTHROPIC_API_KEY".to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply_file(&mut self, file: LlmConfigFile) {
+        if let Some(base_url) = file.base_url {
+            self.base_url = base_url;
+        }
+        if let Some(model) = file.model {
+            self.model = model;
+        }
+        if file.api_key.is_some() {
+            self.api_key = file.api_key;
+        }
+        if file.organization.is_some() {
+            self.organization = file.organization;
+        }
+        if let Some(timeout_secs) = file.timeout_secs {
+            self.timeout_secs = timeout_secs;
+        }
+        if let Some(temperature) = file.temperature {
+            self.temperature = temperature;
+        }
+        if let Some(max_tokens) = file.max_tokens {
+            self.max_tokens = max_tokens;
+        }
+        if let Some(stream) = file.stream {
+            self.stream = stream;
+        }
+        if let Some(structured_output) = file.structured_output {
+            self.structured_output = structured_output;
+        }
+        if let Some(decode_retries) = file.decode_retries {
+            self.decode_retries = decode_retries;
+        }
+        if let Some(retry) = file.retry {
+            self.retry = retry;
+        }
+        if let Some(prices) = file.prices {
+            self.prices.extend(prices);
+        }
+        if let Some(budget) = file.budget {
+            self.budget = budget;
+        }
+        if let Some(cache) = file.cache {
+            self.cache = cache;
+        }
+        if let Some(cassette) = file.cassette {
+            self.cassette = cassette;
+        }
+        if let Some(context) = file.context {
+            self.context = context;
+        }
+        if let Some(routes) = file.routes {
+            self.routes.extend(routes);
+        }
+        if let Some(rate_limit) = file.rate_limit {
+            self.rate_limit = rate_limit;
+        }
+        if let Some(prompts) = file.prompts {
+            self.prompts = prompts;
+        }
+        if let Some(transcript) = file.transcript {
+            self.transcript = transcript;
+        }
+    }
+
+    fn apply_env(&mut self) -> Result<(), LlmError> {
+        if let Some(base_url) = env_value("LLM_BASE_URL")? {
+            self.base_url = base_url;
+        }
+        if let Some(model) = env_value("LLM_MODEL")? {
+            self.model = model;
+        }
+        if let Some(timeout_secs) = env_value("LLM_TIMEOUT_SECS")? {
+            self.timeout_secs = timeout_secs;
+        }
+        if let Some(temperature) = env_value("LLM_TEMPERATURE")? {
+            self.temperature = temperature;
+        }
+        if let Some(max_tokens) = env_value("LLM_MAX_TOKENS")? {
+            self.max_tokens = max_tokens;
+        }
+        if let Some(stream) = env_value("LLM_STREAM")? {
+            self.stream = stream;
+        }
+        if let Some(structured_output) = env_value("LLM_STRUCTURED_OUTPUT")? {
+            self.structured_output = structured_output;
+        }
+        if let Some(decode_retries) = env_value("LLM_DECODE_RETRIES")? {
+            self.decode_retries = decode_retries;
+        }
+        if let Some(limit) = env_value("LLM_CONTEXT_LIMIT")? {
+            self.context.limit = Some(limit);
+        }
+        if let Some(rpm) = env_value("LLM_REQUESTS_PER_MINUTE")? {
+            self.rate_limit.requests_per_minute = Some(rpm);
+        }
+        if let Some(tpm) = env_value("LLM_TOKENS_PER_MINUTE")? {
+            self.rate_limit.tokens_per_minute = Some(tpm);
+        }
+        if let Some(max_attempts) = env_value("LLM_MAX_ATTEMPTS")? {
+            self.retry.max_attempts = max_attempts;
+        }
+        if let Some(max_tokens) = env_value("BUDGET_MAX_TOKENS")? {
+            self.budget.max_tokens = Some(max_tokens);
+        }
+        if let Some(max_cost_usd) = env_value("BUDGET_MAX_USD")? {
+            self.budget.max_cost_usd = Some(max_cost_usd);
+        }
+        if let Some(max_minutes) = env_value("BUDGET_MAX_MINUTES")? {
+            self.budget.max_minutes = Some(max_minutes);
+        }
+        if let Some(mode) = env_value("LLM_CACHE")? {
+            self.cache.mode = mode;
+        }
+        if let Some(dir) = env_value::<String>("LLM_CACHE_DIR")? {
+            self.cache.dir = dir.into();
+        }
+        if let Some(mode) = env_value("LLM_CASSETTE")? {
+            self.cassette.mode = mode;
+        }
+        if let Some(path) = env_value::<String>("LLM_CASSETTE_PATH")? {
+            self.cassette.path = path.into();
+        }
+        if let Some(dir) = env_value::<String>("LLM_PROMPTS_DIR")? {
+            self.prompts.dir = Some(dir.into());
+        }
+        if let Some(enabled) = env_value("LLM_TRANSCRIPT")? {
+            self.transcript.enabled = enabled;
+        }
+        if let Some(dir) = env_value::<String>("LLM_TRANSCRIPT_DIR")? {
+            self.transcript.dir = dir.into();
+        }
+
+        // Keep the provider specific variable names people already have in .env
+        let key_var = match self.provider {
+            LlmProvider::OpenAi => Some("OPEN_AI_KEY"),
+            LlmProvider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            LlmProvider::Ollama => None,
+        };
+        if let Some(api_key) = env_value("LLM_API_KEY")? {
+            self.api_key = Some(api_key);
+        } else if let Some(api_key) = key_var.map(env_value).transpose()?.flatten() {
+            self.api_key = Some(api_key);
+        }
+
+        if self.provider == LlmProvider::OpenAi {
+            if let Some(organization) = env_value("OPEN_AI_ORG")? {
+                self.organization = Some(organization);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Read and parse an env var, treating empty values as unset
+fn env_value<T: FromStr>(name: &str) -> Result<Option<T>, LlmError> {
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| LlmError::ConfigInvalid(format!("{}={}", name, value))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::{context::ContextStrategy, response_cache::CacheMode};
+
+    #[test]
+    fn tests_applying_config_file() {
+        let file = LlmConfig::parse_file(
+            r#"
+            provider = "openai"
+            base_url = "http://localhost:8000/v1/"
+            model = "gpt-4o-mini"
+            temperature = 0.0
+            structured_output = "json_schema"
+            decode_retries = 0
+
+            [retry]
+            max_attempts = 6
+            retry_on = ["rate_limit"]
+
+            [prices.gpt-4o-mini]
+            input_per_million = 0.1
+            output_per_million = 0.4
+
+            [budget]
+            max_cost_usd = 2.5
+
+            [cache]
+            mode = "on"
+
+            [context]
+            limit = 16000
+            strategy = "summarize"
+
+            [rate_limit]
+            requests_per_minute = 60
+            max_concurrent = 2
+
+            [routes.print_backend_webserver_code]
+            model = "gpt-4o"
+            max_tokens = 8192
+            fallback_models = ["gpt-4-turbo"]
+
+            [prompts]
+            dir = "prompts"
+
+            [transcript]
+            enabled = true
+            redact = ["postgres://\\S+"]
+            "#,
+        )
+        .unwrap();
+
+        let mut config = LlmConfig::for_provider(file.provider.unwrap());
+        config.apply_file(file);
+
+        assert_eq!(config.model, "gpt-4o-mini");
+        assert_eq!(config.temperature, 0.0);
+        assert_eq!(config.structured_output, StructuredOutput::JsonSchema);
+        assert_eq!(config.decode_retries, 0);
+        assert_eq!(config.organization, None);
+        assert_eq!(config.retry.max_attempts, 6);
+        assert_eq!(config.retry.initial_backoff_ms, 1_000);
+        assert_eq!(
+            config
+                .prices
+                .lookup("gpt-4o-mini")
+                .unwrap()
+                .input_per_million,
+            0.1
+        );
+        assert_eq!(config.budget.max_cost_usd, Some(2.5));
+        assert_eq!(config.budget.max_tokens, None);
+        assert_eq!(config.cache.mode, CacheMode::On);
+        assert_eq!(config.context.limit, Some(16_000));
+        assert_eq!(config.context.strategy, ContextStrategy::Summarize);
+        assert!(config.context.pin_system);
+        assert_eq!(config.rate_limit.requests_per_minute, Some(60));
+        assert_eq!(config.rate_limit.tokens_per_minute, None);
+        assert_eq!(
+            config
+                .routes
+                .fallback_models("print_backend_webserver_code"),
+            ["gpt-4-turbo".to_string()]
+        );
+        assert_eq!(
+            config.prompts.dir,
+            Some(std::path::PathBuf::from("prompts"))
+        );
+        assert!(config.transcript.enabled);
+        assert_eq!(config.transcript.redact, [r"postgres://\S+"]);
+        assert_eq!(config.cache.dir, std::path::PathBuf::from(".llm_cache"));
+        assert_eq!(
+            config.endpoint("chat/completions"),
+            "http://localhost:8000/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn tests_requiring_key_for_hosted_apis() {
+        let hosted = LlmConfig::for_provider(LlmProvider::OpenAi);
+        assert!(matches!(hosted.validate(), Err(LlmError::ConfigMissing(_))));
+
+        let mut local = LlmConfig::for_provider(LlmProvider::OpenAi);
+        local.base_url = "http://localhost:8000/v1".to_string();
+        assert!(local.validate().is_ok());
+
+        let mut replay = LlmConfig::for_provider(LlmProvider::Anthropic);
+        replay.cassette.mode = CassetteMode::Replay;
+        assert!(replay.validate().is_ok());
+    }
+
+    #[test]
+    fn tests_rejecting_unknown_config_keys() {
+        assert!(LlmConfig::parse_file("modle = \"gpt-4\"").is_err());
+    }
+}
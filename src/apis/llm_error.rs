@@ -0,0 +1,154 @@
+use std::{fmt, time::Duration};
+
+use crate::models::general::llm::{APIError, APIErrorField, APIErrorResponse};
+
+#[derive(Debug)]
+pub enum LlmError {
+    // A required setting such as an API key was not provided
+    ConfigMissing(String),
+    // A setting was provided but could not be used
+    ConfigInvalid(String),
+    // The request never produced an HTTP response
+    Transport(reqwest::Error),
+    // The provider answered with a non-success status
+    Status {
+        status: u16,
+        api_error: Option<APIError>,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    // The provider answered but returned no completion
+    EmptyChoices,
+    // A streamed reply ended before the provider marked it complete
+    TruncatedStream,
+    // The body could not be decoded into the expected type
+    Decode {
+        source: serde_json::Error,
+        body: String,
+    },
+    // A token, spend or wall-clock limit was reached before the call was made
+    BudgetExceeded(String),
+    // A cassette could not be read or written, or has no matching recording
+    Cassette(String),
+    // The prompt does not fit the model's context window even after trimming
+    ContextOverflow {
+        tokens: usize,
+        limit: usize,
+    },
+}
+
+impl LlmError {
+    // Build a status error, keeping the provider's error message when the body has one
+    pub fn from_status(status: u16, body: String, retry_after: Option<Duration>) -> Self {
+        let api_error = serde_json::from_str::<APIErrorResponse>(&body)
+            .ok()
+            .map(|res| match res.error {
+                APIErrorField::Detailed(api_error) => api_error,
+                APIErrorField::Message(message) => APIError {
+                    message,
+                    error_type: None,
+                    code: None,
+                },
+            });
+
+        Self::Status {
+            status,
+            api_error,
+            body,
+            retry_after,
+        }
+    }
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfigMissing(setting) => write!(f, "LLM config missing: {}", setting),
+            Self::ConfigInvalid(reason) => write!(f, "LLM config invalid: {}", reason),
+            Self::Transport(e) => write!(f, "LLM request failed: {}", e),
+            Self::Status {
+                status,
+                api_error: Some(api_error),
+                ..
+            } => match &api_error.error_type {
+                Some(error_type) => write!(
+                    f,
+                    "LLM API returned {} ({}): {}",
+                    status, error_type, api_error.message
+                ),
+                None => write!(f, "LLM API returned {}: {}", status, api_error.message),
+            },
+            Self::Status { status, body, .. } => {
+                write!(f, "LLM API returned {}: {}", status, preview(body))
+            }
+            Self::EmptyChoices => write!(f, "LLM API returned no choices"),
+            Self::TruncatedStream => write!(f, "LLM stream closed before the reply was complete"),
+            Self::Decode { source, body } => write!(
+                f,
+                "Failed to decode LLM response: {} in {}",
+                source,
+                preview(body)
+            ),
+            Self::BudgetExceeded(reason) => write!(f, "Budget exhausted: {}", reason),
+            Self::Cassette(reason) => write!(f, "Cassette error: {}", reason),
+            Self::ContextOverflow { tokens, limit } => write!(
+                f,
+                "Prompt needs {} tokens but only {} fit in the context window",
+                tokens, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LlmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            Self::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for LlmError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Transport(e)
+    }
+}
+
+// Keep error messages readable when a body is a whole source file
+fn preview(body: &str) -> String {
+    const MAX_CHARS: usize = 200;
+
+    if body.chars().count() <= MAX_CHARS {
+        return body.to_string();
+    }
+
+    let cut: String = body.chars().take(MAX_CHARS).collect();
+    format!("{}...", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parsing_api_error_bodies() {
+        let openai_body = r#"{"error": {"message": "Rate limit reached", "type": "requests", "code": "rate_limit_exceeded"}}"#;
+        let err = LlmError::from_status(429, openai_body.to_string(), None);
+        assert_eq!(
+            err.to_string(),
+            "LLM API returned 429 (requests): Rate limit reached"
+        );
+
+        let ollama_body = r#"{"error": "model 'llama3' not found"}"#;
+        let err = LlmError::from_status(404, ollama_body.to_string(), None);
+        assert_eq!(
+            err.to_string(),
+            "LLM API returned 404: model 'llama3' not found"
+        );
+
+        let err = LlmError::from_status(502, "Bad Gateway".to_string(), None);
+        assert_eq!(err.to_string(), "LLM API returned 502: Bad Gateway");
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{dev::ServerHandle, web, App, HttpResponse, HttpServer};
+use serde_json::{json, Value};
+
+use super::{llm_client::LlmProvider, llm_config::LlmConfig, retry::RetryPolicy};
+
+// What the mock answers to one request
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockReply {
+    Completion(String),
+    // An OpenAI style error body with the given status, e.g. 429 or 500
+    Error { status: u16, message: String },
+}
+
+// Replies per ai_function, served in order. The last reply for a function
+// keeps being served once the ones before it are used up.
+#[derive(Debug, Default, Clone)]
+pub struct MockScript {
+    replies: HashMap<String, VecDeque<MockReply>>,
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reply(self, ai_function: &str, content: &str) -> Self {
+        self.push(ai_function, MockReply::Completion(content.to_string()))
+    }
+
+    pub fn fail(self, ai_function: &str, status: u16, message: &str) -> Self {
+        self.push(
+            ai_function,
+            MockReply::Error {
+                status,
+                message: message.to_string(),
+            },
+        )
+    }
+
+    fn push(mut self, ai_function: &str, reply: MockReply) -> Self {
+        self.replies
+            .entry(ai_function.to_string())
+            .or_default()
+            .push_back(reply);
+        self
+    }
+
+    fn next(&mut self, ai_function: &str) -> Option<MockReply> {
+        let queue = self.replies.get_mut(ai_function)?;
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    script: Mutex<MockScript>,
+    // ai_function of every request received, in order
+    calls: Mutex<Vec<String>>,
+    requests: Mutex<Vec<Value>>,
+}
+
+// OpenAI compatible chat completions server answering from a script
+pub struct MockLlmServer {
+    base_url: String,
+    handle: ServerHandle,
+    state: Arc<MockState>,
+}
+
+impl MockLlmServer {
+    pub async fn start(script: MockScript) -> Self {
+        let state = Arc::new(MockState {
+            script: Mutex::new(script),
+            calls: Mutex::new(vec![]),
+            requests: Mutex::new(vec![]),
+        });
+
+        let app_state = web::Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .route("/v1/chat/completions", web::post().to(chat_completions))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind mock LLM server");
+
+        let base_url = format!("http://{}/v1", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Self {
+            base_url,
+            handle,
+            state,
+        }
+    }
+
+    // Config pointing at this server, retrying without delay
+    pub fn config(&self) -> LlmConfig {
+        let mut config = LlmConfig::for_provider(LlmProvider::OpenAi);
+        config.base_url = self.base_url.clone();
+        config.retry = RetryPolicy {
+            initial_backoff_ms: 0,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        config
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    // Request bodies as received, in order
+    pub fn requests(&self) -> Vec<Value> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    // Not graceful, that would wait for the clients' pooled connections to time out
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+async fn chat_completions(state: web::Data<MockState>, body: web::Json<Value>) -> HttpResponse {
+    let prompt = body["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message["content"].as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    // With history sent along, the function being called is in the last message
+    let last_message = body["messages"]
+        .as_array()
+        .and_then(|messages| messages.last())
+        .and_then(|message| message["content"].as_str())
+        .unwrap_or_default();
+
+    let ai_function = ai_function_name(last_message)
+        .unwrap_or("unknown")
+        .to_string();
+    state.calls.lock().unwrap().push(ai_function.clone());
+    state.requests.lock().unwrap().push(body.0.clone());
+
+    let reply = state.script.lock().unwrap().next(&ai_function);
+
+    match reply {
+        Some(MockReply::Completion(content)) => {
+            let usage = json!({
+                "prompt_tokens": prompt.len() / 4,
+                "completion_tokens": content.len() / 4
+            });
+
+            if body["stream"].as_bool() == Some(true) {
+                let events = [
+                    json!({"choices": [{"delta": {"content": content}}]}),
+                    json!({"choices": [], "usage": usage}),
+                ]
+                .iter()
+                .map(|event| format!("data: {}\n\n", event))
+                .collect::<String>();
+
+                return HttpResponse::Ok()
+                    .content_type("text/event-stream")
+                    .body(format!("{}data: [DONE]\n\n", events));
+            }
+
+            // A forced tool call gets the scripted content back as its arguments
+            let message = match body["tool_choice"]["function"]["name"].as_str() {
+                Some(name) => json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_mock",
+                        "type": "function",
+                        "function": {"name": name, "arguments": content}
+                    }]
+                }),
+                None => json!({"role": "assistant", "content": content}),
+            };
+
+            HttpResponse::Ok().json(json!({
+                "choices": [{"message": message}],
+                "usage": usage
+            }))
+        }
+        Some(MockReply::Error { status, message }) => {
+            let status = actix_web::http::StatusCode::from_u16(status)
+                .expect("Scripted an invalid status code");
+            HttpResponse::build(status).json(json!({
+                "error": {"message": message, "type": "mock_error"}
+            }))
+        }
+        None => HttpResponse::NotFound().json(json!({
+            "error": {"message": format!("no scripted reply for {}", ai_function)}
+        })),
+    }
+}
+
+// extend_ai_function prompts start with "FUNCTION: pub fn <name>(...", long
+// signatures are wrapped so the name may follow on the next line
+pub fn ai_function_name(prompt: &str) -> Option<&str> {
+    let function = prompt.split("FUNCTION:").nth(1)?;
+    let after_fn = function
+        .split_once("fn")
+        .map(|(_, rest)| rest.trim_start())?;
+
+    let end = after_fn
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(after_fn.len());
+
+    Some(&after_fn[..end]).filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai_functions::aifunc_managing::convert_user_input_to_goal,
+        apis::llm_client::create_llm_client,
+        helpers::generals::{ai_task_request, extend_ai_function},
+        models::agent_basic::{basic_agent::BasicAgent, basic_traits::BasicTrait},
+    };
+
+    #[test]
+    fn tests_finding_ai_function_in_prompt() {
+        let prompt =
+            extend_ai_function(convert_user_input_to_goal("a todo app"), Some("a todo app"))
+                .content;
+
+        assert_eq!(
+            ai_function_name(&prompt),
+            Some("convert_user_input_to_goal")
+        );
+        assert_eq!(
+            ai_function_name("FUNCTION: pub fn\nprint_improved_webserver_code(_input : & str)"),
+            Some("print_improved_webserver_code")
+        );
+        assert_eq!(ai_function_name("Give me a short response"), None);
+    }
+
+    #[tokio::test]
+    async fn tests_serving_scripted_replies_and_failures() {
+        let script = MockScript::new()
+            .fail("convert_user_input_to_goal", 429, "Rate limit reached")
+            .reply("convert_user_input_to_goal", "build a todo list backend");
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+
+        let mut agent = BasicAgent::new("Test".to_string(), "Managing Agent".to_string());
+
+        let goal = ai_task_request(
+            llm.as_ref(),
+            "a todo app".to_string(),
+            &mut agent,
+            "convert_user_input_to_goal",
+            convert_user_input_to_goal,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(goal, "build a todo list backend");
+        assert_eq!(
+            server.calls(),
+            vec!["convert_user_input_to_goal", "convert_user_input_to_goal"]
+        );
+
+        server.stop().await;
+    }
+}
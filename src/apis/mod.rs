@@ -1 +1,17 @@
+pub mod budget;
 pub mod call_request;
+pub mod cassette;
+pub mod context;
+pub mod llm_client;
+pub mod llm_config;
+pub mod llm_error;
+#[cfg(test)]
+pub mod mock_llm;
+pub mod prompts;
+pub mod response_cache;
+pub mod retry;
+pub mod routing;
+pub mod session;
+pub mod structured_output;
+pub mod transcript;
+pub mod usage;
@@ -0,0 +1,332 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::ai_functions::{find_ai_function, AiFunction, AI_FUNCTIONS};
+
+use super::llm_error::LlmError;
+
+// Replaced with the ai_function's input when the prompt is sent
+const INPUT_PLACEHOLDER: &str = "input";
+
+// A TOML template, its variables fill {{name}} placeholders when loaded
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    // Defaults to a hash of the template like Markdown files
+    version: Option<String>,
+    template: String,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PromptTemplate {
+    path: PathBuf,
+    version: String,
+    text: String,
+}
+
+// Where the prompt sent for an ai_function comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptSource {
+    BuiltIn,
+    File { path: PathBuf, version: String },
+}
+
+impl fmt::Display for PromptSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BuiltIn => write!(f, "built-in"),
+            Self::File { path, version } => write!(f, "{} (version {})", path.display(), version),
+        }
+    }
+}
+
+impl PromptTemplate {
+    fn source(&self) -> PromptSource {
+        PromptSource::File {
+            path: self.path.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+// Prompt templates that replace the built-in ai_function text, read from
+// <dir>/<ai_function>.toml or <dir>/<ai_function>.md
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptRegistry {
+    pub dir: Option<PathBuf>,
+    #[serde(skip)]
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl PromptRegistry {
+    // Read every template in dir. Templates for unknown ai_functions or with
+    // unknown placeholders are errors, they would otherwise be silently unused.
+    pub fn load(&mut self) -> Result<(), LlmError> {
+        self.templates.clear();
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let entries = fs::read_dir(dir)
+            .map_err(|e| LlmError::ConfigInvalid(format!("prompts {}: {}", dir.display(), e)))?;
+
+        let mut templates: HashMap<String, PromptTemplate> = HashMap::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| LlmError::ConfigInvalid(format!("prompts {}: {}", dir.display(), e)))?
+                .path();
+
+            let Some(name) = template_name(&path) else {
+                continue;
+            };
+            let invalid = |reason: String| {
+                LlmError::ConfigInvalid(format!("prompt {}: {}", path.display(), reason))
+            };
+
+            if find_ai_function(name).is_none() {
+                return Err(invalid(format!("there is no ai_function named {}", name)));
+            }
+            if let Some(other) = templates.get(name) {
+                return Err(invalid(format!(
+                    "{} already defines {}",
+                    other.path.display(),
+                    name
+                )));
+            }
+
+            let contents = fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+            let (version, text) = parse_template(&path, &contents).map_err(invalid)?;
+
+            templates.insert(
+                name.to_string(),
+                PromptTemplate {
+                    path,
+                    version,
+                    text,
+                },
+            );
+        }
+
+        self.templates = templates;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn insert(&mut self, ai_function: &str, text: &str) {
+        self.templates.insert(
+            ai_function.to_string(),
+            PromptTemplate {
+                path: PathBuf::from(format!("{}.md", ai_function)),
+                version: content_version(text),
+                text: text.to_string(),
+            },
+        );
+    }
+
+    // The function text to send for ai_function, the built-in one unless a template overrides it
+    pub fn render(&self, ai_function: &str, ai_func: AiFunction, input: &str) -> String {
+        match self.templates.get(ai_function) {
+            Some(template) => template
+                .text
+                .replace(&placeholder(INPUT_PLACEHOLDER), input),
+            None => ai_func(input).to_string(),
+        }
+    }
+
+    // Whether render puts the input into the text, only templates can
+    pub fn embeds_input(&self, ai_function: &str) -> bool {
+        self.templates
+            .get(ai_function)
+            .is_some_and(|template| template.text.contains(&placeholder(INPUT_PLACEHOLDER)))
+    }
+
+    // The effective prompt for ai_function with {{input}} left in place
+    pub fn show(&self, ai_function: &str) -> Option<(PromptSource, String)> {
+        if let Some(template) = self.templates.get(ai_function) {
+            return Some((template.source(), template.text.clone()));
+        }
+
+        find_ai_function(ai_function)
+            .map(|ai_func| (PromptSource::BuiltIn, ai_func("").to_string()))
+    }
+
+    // Every ai_function and where its prompt comes from
+    pub fn list(&self) -> Vec<(&'static str, PromptSource)> {
+        AI_FUNCTIONS
+            .iter()
+            .map(|(ai_function, _)| {
+                let source = match self.templates.get(*ai_function) {
+                    Some(template) => template.source(),
+                    None => PromptSource::BuiltIn,
+                };
+                (*ai_function, source)
+            })
+            .collect()
+    }
+}
+
+// The ai_function a template file is for, other files in the directory are ignored
+fn template_name(path: &Path) -> Option<&str> {
+    match path.extension()?.to_str()? {
+        "toml" | "md" => path.file_stem()?.to_str(),
+        _ => None,
+    }
+}
+
+fn placeholder(name: &str) -> String {
+    format!("{{{{{}}}}}", name)
+}
+
+// Templates without an explicit version are told apart by their contents
+fn content_version(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text))[..8].to_string()
+}
+
+// The version and text of a template. Markdown files are the template as is,
+// TOML ones can also set a version and define variables. Variables are filled
+// in now, {{input}} is kept for when the prompt is sent.
+fn parse_template(path: &Path, contents: &str) -> Result<(String, String), String> {
+    let (version, template, variables) = if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        let file: TemplateFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+        (file.version, file.template, file.variables)
+    } else {
+        (None, contents.to_string(), HashMap::new())
+    };
+
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+
+        text.push_str(&rest[..start]);
+        match variables.get(name) {
+            Some(value) => text.push_str(value),
+            None if name == INPUT_PLACEHOLDER => text.push_str(&placeholder(name)),
+            None => return Err(format!("unknown placeholder {}", placeholder(name))),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    text.push_str(rest);
+
+    let version = version.unwrap_or_else(|| content_version(&text));
+    Ok((version, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_functions::aifunc_backend::print_backend_webserver_code;
+    use std::env;
+
+    fn prompts_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("prompts_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn tests_loading_templates_over_built_in_prompts() {
+        let dir = prompts_dir(
+            "loading",
+            &[
+                (
+                    "print_backend_webserver_code.toml",
+                    r#"
+                    version = "axum-1"
+                    template = "Write an axum server for {{input}} using only {{ allowed_crates }}"
+
+                    [variables]
+                    allowed_crates = "axum, tokio, serde"
+                    "#,
+                ),
+                ("print_fixed_code.md", "Fix this code:\n{{ input }}\n"),
+                ("README.txt", "not a prompt"),
+            ],
+        );
+        let mut registry = PromptRegistry {
+            dir: Some(dir.clone()),
+            ..PromptRegistry::default()
+        };
+        registry.load().unwrap();
+
+        assert_eq!(
+            registry.render(
+                "print_backend_webserver_code",
+                print_backend_webserver_code,
+                "a todo app"
+            ),
+            "Write an axum server for a todo app using only axum, tokio, serde"
+        );
+        assert_eq!(
+            registry.show("print_fixed_code"),
+            Some((
+                PromptSource::File {
+                    path: dir.join("print_fixed_code.md"),
+                    version: content_version("Fix this code:\n{{input}}\n"),
+                },
+                "Fix this code:\n{{input}}\n".to_string()
+            ))
+        );
+        assert_eq!(
+            registry.list()[5],
+            (
+                "print_backend_webserver_code",
+                PromptSource::File {
+                    path: dir.join("print_backend_webserver_code.toml"),
+                    version: "axum-1".to_string(),
+                }
+            )
+        );
+
+        // Everything without a template keeps the ai_function's own text
+        let (source, text) = registry.show("print_rest_api_endpoints").unwrap();
+        assert_eq!(source, PromptSource::BuiltIn);
+        assert!(text.contains("print_rest_api_endpoints"));
+        assert_eq!(registry.list().len(), AI_FUNCTIONS.len());
+        assert_eq!(registry.show("print_poem"), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tests_rejecting_unusable_templates() {
+        for (name, files) in [
+            (
+                "unknown_function",
+                [("print_poem.md", "A poem about {{input}}")],
+            ),
+            (
+                "unknown_placeholder",
+                [("print_fixed_code.md", "Fix {{input}} in {{language}}")],
+            ),
+        ] {
+            let dir = prompts_dir(name, &files);
+            let mut registry = PromptRegistry {
+                dir: Some(dir.clone()),
+                ..PromptRegistry::default()
+            };
+
+            assert!(matches!(registry.load(), Err(LlmError::ConfigInvalid(_))));
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
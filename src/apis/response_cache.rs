@@ -0,0 +1,198 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use strum_macros::{Display, EnumString};
+
+use crate::models::general::llm::{ChatOptions, LlmResponse, Message};
+
+use super::llm_config::LlmConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    // Always call the provider and store nothing
+    Off,
+    // Serve stored completions and store new ones
+    On,
+    // Call the provider and overwrite what is stored
+    Refresh,
+}
+
+// Completions stored on disk, one file per prompt hash
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseCache {
+    pub mode: CacheMode,
+    pub dir: PathBuf,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self {
+            mode: CacheMode::Off,
+            dir: PathBuf::from(".llm_cache"),
+        }
+    }
+}
+
+// Everything that changes what the provider would answer
+#[derive(Serialize)]
+struct CacheKey<'a> {
+    provider: String,
+    // Two OpenAI compatible servers can serve the same model name
+    base_url: &'a str,
+    model: &'a str,
+    temperature: f32,
+    max_tokens: u32,
+    messages: &'a [Message],
+    // Left out for plain chats so their keys stay the same
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<&'a ChatOptions>,
+}
+
+// The prompt is kept next to the completion so entries can be inspected by hand
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    model: String,
+    messages: Vec<Message>,
+    response: LlmResponse,
+}
+
+impl ResponseCache {
+    pub fn key(config: &LlmConfig, messages: &[Message], options: &ChatOptions) -> String {
+        let key = CacheKey {
+            provider: config.provider.to_string(),
+            base_url: config.base_url.trim_end_matches('/'),
+            model: &config.model,
+            temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            messages,
+            options: Some(options).filter(|options| **options != ChatOptions::default()),
+        };
+
+        let json = serde_json::to_vec(&key).expect("Cache key is always serializable");
+        format!("{:x}", Sha256::digest(json))
+    }
+
+    // Unreadable entries count as misses, the next call overwrites them
+    pub fn get(&self, key: &str) -> Option<LlmResponse> {
+        if self.mode != CacheMode::On {
+            return None;
+        }
+
+        let contents = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str::<CacheEntry>(&contents)
+            .ok()
+            .map(|entry| entry.response)
+    }
+
+    pub fn put(
+        &self,
+        key: &str,
+        model: &str,
+        messages: &[Message],
+        response: &LlmResponse,
+    ) -> io::Result<()> {
+        if self.mode == CacheMode::Off {
+            return Ok(());
+        }
+
+        let entry = CacheEntry {
+            model: model.to_string(),
+            messages: messages.to_vec(),
+            response: response.clone(),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(key), serde_json::to_string_pretty(&entry)?)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apis::llm_client::LlmProvider,
+        models::general::llm::{ResponseFormat, Usage},
+    };
+
+    fn messages() -> Vec<Message> {
+        vec![Message {
+            role: "system".to_string(),
+            content: "FUNCTION: print_project_scope".to_string(),
+        }]
+    }
+
+    #[test]
+    fn tests_keying_by_model_and_sampling_params() {
+        let config = LlmConfig::for_provider(LlmProvider::OpenAi);
+        let plain = ChatOptions::default();
+        let key = ResponseCache::key(&config, &messages(), &plain);
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(
+            key,
+            ResponseCache::key(&config.clone(), &messages(), &plain)
+        );
+
+        let mut warmer = config.clone();
+        warmer.temperature = 0.7;
+        assert_ne!(key, ResponseCache::key(&warmer, &messages(), &plain));
+
+        let json_mode = ChatOptions {
+            response_format: Some(ResponseFormat::JsonObject),
+            ..ChatOptions::default()
+        };
+        assert_ne!(key, ResponseCache::key(&config, &messages(), &json_mode));
+
+        let mut other_model = config.clone();
+        other_model.model = "gpt-4o".to_string();
+        assert_ne!(key, ResponseCache::key(&other_model, &messages(), &plain));
+
+        let mut other_server = config;
+        other_server.base_url = "http://localhost:11434/v1".to_string();
+        assert_ne!(key, ResponseCache::key(&other_server, &messages(), &plain));
+    }
+
+    #[test]
+    fn tests_storing_and_refreshing_completions() {
+        let dir = std::env::temp_dir().join(format!("llm_cache_test_{}", std::process::id()));
+        let response = LlmResponse {
+            content: "{\"is_crud_required\": true}".to_string(),
+            usage: Usage::default(),
+            tool_calls: vec![],
+        };
+
+        let off = ResponseCache {
+            mode: CacheMode::Off,
+            dir: dir.clone(),
+        };
+        off.put("abc", "gpt-4-turbo", &messages(), &response)
+            .unwrap();
+        assert!(!dir.exists());
+
+        let refresh = ResponseCache {
+            mode: CacheMode::Refresh,
+            ..off.clone()
+        };
+        refresh
+            .put("abc", "gpt-4-turbo", &messages(), &response)
+            .unwrap();
+        assert_eq!(refresh.get("abc"), None);
+
+        let on = ResponseCache {
+            mode: CacheMode::On,
+            ..off
+        };
+        assert_eq!(on.get("abc"), Some(response));
+        assert_eq!(on.get("missing"), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
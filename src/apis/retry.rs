@@ -0,0 +1,209 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::helpers::command_line::PrintCommand;
+
+use super::llm_error::LlmError;
+
+// Kinds of failure a policy can choose to retry
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryClass {
+    // 429 Too Many Requests
+    RateLimit,
+    // 5xx and 408 responses
+    ServerError,
+    // Connection failures, timeouts and streams cut off mid-reply
+    Transport,
+    // Empty choices or a body that could not be decoded
+    InvalidResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    // Total attempts including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub multiplier: f64,
+    // Fraction of the delay to randomise, 0.0 disables jitter
+    pub jitter: f64,
+    pub retry_on: Vec<RetryClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            retry_on: vec![
+                RetryClass::RateLimit,
+                RetryClass::ServerError,
+                RetryClass::Transport,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retryable(&self, error: &LlmError) -> bool {
+        classify(error).is_some_and(|class| self.retry_on.contains(&class))
+    }
+
+    // Delay before the given retry, 1 being the first retry
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay_ms = (self.initial_backoff_ms as f64 * self.multiplier.powi(exponent))
+            .min(self.max_backoff_ms as f64);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+
+        Duration::from_millis((delay_ms * factor) as u64)
+    }
+
+    // A Retry-After from the server is a floor, never wait less than it asks
+    fn delay_for(&self, error: &LlmError, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+
+        match error {
+            LlmError::Status {
+                retry_after: Some(retry_after),
+                ..
+            } => backoff.max(*retry_after),
+            _ => backoff,
+        }
+    }
+}
+
+// Map an error onto a retry class, None for permanent failures
+pub fn classify(error: &LlmError) -> Option<RetryClass> {
+    match error {
+        LlmError::Status { status: 429, .. } => Some(RetryClass::RateLimit),
+        LlmError::Status { status, .. } if *status == 408 || *status >= 500 => {
+            Some(RetryClass::ServerError)
+        }
+        LlmError::Transport(_) | LlmError::TruncatedStream => Some(RetryClass::Transport),
+        LlmError::EmptyChoices | LlmError::Decode { .. } => Some(RetryClass::InvalidResponse),
+        _ => None,
+    }
+}
+
+// Run an LLM call, retrying failures the policy allows and reporting each retry
+pub async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    agent_position: &str,
+    mut call: F,
+) -> Result<T, LlmError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, LlmError>>,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 1;
+
+    loop {
+        match call().await {
+            Ok(response) => return Ok(response),
+            Err(e) if attempt < max_attempts && policy.is_retryable(&e) => {
+                let delay = policy.delay_for(&e, attempt);
+
+                let issue = format!(
+                    "LLM call failed ({}), retrying in {:.1}s (attempt {}/{})",
+                    e,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    max_attempts
+                );
+                PrintCommand::Issue.print_agent_message(agent_position, &issue);
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn instant_policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff_ms: 0,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn tests_classifying_errors() {
+        let policy = RetryPolicy::default();
+
+        let rate_limited = LlmError::from_status(429, String::new(), None);
+        let unavailable = LlmError::from_status(503, String::new(), None);
+        let bad_key = LlmError::from_status(401, String::new(), None);
+
+        assert!(policy.is_retryable(&rate_limited));
+        assert!(policy.is_retryable(&unavailable));
+        assert!(!policy.is_retryable(&bad_key));
+        assert!(!policy.is_retryable(&LlmError::EmptyChoices));
+    }
+
+    #[test]
+    fn tests_backoff_growth_and_retry_after() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+
+        let rate_limited = LlmError::from_status(429, String::new(), Some(Duration::from_secs(12)));
+        assert_eq!(policy.delay_for(&rate_limited, 1), Duration::from_secs(12));
+    }
+
+    #[tokio::test]
+    async fn tests_retrying_until_success() {
+        let calls = AtomicU32::new(0);
+
+        let res = with_retry(&instant_policy(), "Test Agent", || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(LlmError::from_status(500, String::new(), None)),
+                _ => Ok("done".to_string()),
+            }
+        })
+        .await;
+
+        assert_eq!(res.unwrap(), "done");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn tests_not_retrying_permanent_errors() {
+        let calls = AtomicU32::new(0);
+
+        let res: Result<String, LlmError> = with_retry(&instant_policy(), "Test Agent", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(LlmError::from_status(400, String::new(), None))
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
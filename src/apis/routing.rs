@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::models::general::llm::ChatOptions;
+
+use super::llm_error::LlmError;
+
+// Sampling settings for one ai_function, unset fields keep the config's values
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Route {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub seed: Option<u64>,
+    pub stop: Option<Vec<String>>,
+    // Tried in order when the model still fails after its retries
+    pub fallback_models: Vec<String>,
+}
+
+// Routes keyed by ai_function name, as produced by get_function_string!
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct RoutingTable(HashMap<String, Route>);
+
+impl RoutingTable {
+    #[cfg(test)]
+    pub fn insert(&mut self, ai_function: &str, route: Route) {
+        self.0.insert(ai_function.to_string(), route);
+    }
+
+    pub fn extend(&mut self, other: RoutingTable) {
+        self.0.extend(other.0);
+    }
+
+    pub fn get(&self, ai_function: &str) -> Option<&Route> {
+        self.0.get(ai_function)
+    }
+
+    // Options for a call to ai_function, with the route's settings filled in
+    // wherever the caller left them unset
+    pub fn options_for(&self, ai_function: &str, options: &ChatOptions) -> ChatOptions {
+        let mut options = options.clone();
+        let Some(route) = self.get(ai_function) else {
+            return options;
+        };
+
+        options.model = options.model.or_else(|| route.model.clone());
+        options.temperature = options.temperature.or(route.temperature);
+        options.max_tokens = options.max_tokens.or(route.max_tokens);
+        options.seed = options.seed.or(route.seed);
+        options.stop = options.stop.or_else(|| route.stop.clone());
+        options
+    }
+
+    pub fn fallback_models(&self, ai_function: &str) -> &[String] {
+        self.get(ai_function)
+            .map(|route| route.fallback_models.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+// Another model may succeed where this one failed, but not past spending or config limits
+pub fn allows_fallback(error: &LlmError) -> bool {
+    matches!(
+        error,
+        LlmError::Transport(_)
+            | LlmError::Status { .. }
+            | LlmError::EmptyChoices
+            | LlmError::Decode { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_filling_options_from_route() {
+        let mut routes = RoutingTable::default();
+        routes.insert(
+            "print_backend_webserver_code",
+            Route {
+                model: Some("gpt-4o".to_string()),
+                max_tokens: Some(8192),
+                seed: Some(7),
+                fallback_models: vec!["gpt-4-turbo".to_string()],
+                ..Route::default()
+            },
+        );
+
+        let requested = ChatOptions {
+            max_tokens: Some(2048),
+            ..ChatOptions::default()
+        };
+        let options = routes.options_for("print_backend_webserver_code", &requested);
+
+        assert_eq!(options.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(options.max_tokens, Some(2048));
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.temperature, None);
+        assert_eq!(
+            routes.fallback_models("print_backend_webserver_code"),
+            ["gpt-4-turbo".to_string()]
+        );
+
+        assert_eq!(
+            routes.options_for("convert_user_input_to_goal", &ChatOptions::default()),
+            ChatOptions::default()
+        );
+        assert!(routes
+            .fallback_models("convert_user_input_to_goal")
+            .is_empty());
+    }
+}
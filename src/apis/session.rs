@@ -0,0 +1,293 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::models::general::llm::{Message, Usage};
+
+use super::{
+    call_request::http_client, context::count_tokens, llm_config::LlmConfig, llm_error::LlmError,
+    transcript::Transcript, usage::UsageLedger,
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+// Limits shared by every agent using the session, None means unlimited
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests_per_minute: Option<u32>,
+    // Counted as prompt tokens plus max_tokens until the real usage is known
+    pub tokens_per_minute: Option<u64>,
+    // Requests in flight at the same time
+    pub max_concurrent: Option<usize>,
+}
+
+// One request sent in the last minute
+#[derive(Debug)]
+struct SentRequest {
+    id: u64,
+    sent: Instant,
+    tokens: u64,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    requests: VecDeque<SentRequest>,
+    next_id: u64,
+}
+
+impl Window {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .requests
+            .front()
+            .is_some_and(|request| now.duration_since(request.sent) >= WINDOW)
+        {
+            self.requests.pop_front();
+        }
+    }
+
+    fn tokens(&self) -> u64 {
+        self.requests.iter().map(|request| request.tokens).sum()
+    }
+}
+
+// Holds requests back until they fit the shared limits
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    window: Mutex<Window>,
+    // None when concurrency is unlimited
+    slots: Option<Semaphore>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            slots: limit
+                .max_concurrent
+                .map(|slots| Semaphore::new(slots.max(1))),
+            window: Mutex::new(Window::default()),
+            limit,
+        }
+    }
+
+    // Wait for a concurrency slot and room in the per-minute windows
+    pub async fn acquire(&self, estimated_tokens: u64) -> RatePermit<'_> {
+        let slot = match &self.slots {
+            Some(slots) => Some(slots.acquire().await.expect("Semaphore is never closed")),
+            None => None,
+        };
+
+        loop {
+            match self.try_reserve(estimated_tokens, Instant::now()) {
+                Ok(id) => {
+                    return RatePermit {
+                        limiter: self,
+                        id,
+                        settled: false,
+                        _slot: slot,
+                    }
+                }
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    // Reserve a place in the window, or say how long until the oldest request leaves it
+    fn try_reserve(&self, tokens: u64, now: Instant) -> Result<u64, Duration> {
+        let mut window = self.window.lock().unwrap();
+        window.prune(now);
+
+        let over_requests = self
+            .limit
+            .requests_per_minute
+            .is_some_and(|rpm| window.requests.len() as u64 >= u64::from(rpm));
+        // A request larger than the whole budget still goes out once the window is empty
+        let over_tokens = self
+            .limit
+            .tokens_per_minute
+            .is_some_and(|tpm| !window.requests.is_empty() && window.tokens() + tokens > tpm);
+
+        if over_requests || over_tokens {
+            let oldest = window
+                .requests
+                .front()
+                .expect("Limits are only hit by sent requests");
+            return Err(WINDOW.saturating_sub(now.duration_since(oldest.sent)));
+        }
+
+        let id = window.next_id;
+        window.next_id += 1;
+        window.requests.push_back(SentRequest {
+            id,
+            sent: now,
+            tokens,
+        });
+
+        Ok(id)
+    }
+
+    fn settle(&self, id: u64, tokens: u64) {
+        let mut window = self.window.lock().unwrap();
+        if let Some(request) = window.requests.iter_mut().find(|request| request.id == id) {
+            request.tokens = tokens;
+        }
+    }
+}
+
+// Permission to send one request, the concurrency slot is freed on drop
+pub struct RatePermit<'a> {
+    limiter: &'a RateLimiter,
+    id: u64,
+    settled: bool,
+    _slot: Option<SemaphorePermit<'a>>,
+}
+
+impl RatePermit<'_> {
+    // Swap the estimate for what the request actually used
+    pub fn settle(mut self, usage: Usage) {
+        self.limiter
+            .settle(self.id, u64::from(usage.total_tokens()));
+        self.settled = true;
+    }
+}
+
+// A request that failed used no tokens, so its estimate must not hold back the retry.
+// It still counts towards requests per minute.
+impl Drop for RatePermit<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.limiter.settle(self.id, 0);
+        }
+    }
+}
+
+// Everything one run shares across agents: config, a pooled HTTP client,
+// usage, the transcript and the rate limits. Created once and held by the provider client.
+#[derive(Debug)]
+pub struct LlmSession {
+    pub config: LlmConfig,
+    pub http: Client,
+    pub usage: UsageLedger,
+    pub transcript: Transcript,
+    limiter: RateLimiter,
+}
+
+impl LlmSession {
+    pub fn new(config: LlmConfig) -> Result<Self, LlmError> {
+        Ok(Self {
+            http: http_client(&config)?,
+            limiter: RateLimiter::new(config.rate_limit.clone()),
+            usage: UsageLedger::default(),
+            transcript: Transcript::new(&config)?,
+            config,
+        })
+    }
+
+    // Wait until a request with these messages fits the limits. Tokens are
+    // only counted when there is a token limit, counting long prompts is slow.
+    pub async fn acquire(
+        &self,
+        model: &str,
+        messages: &[Message],
+        max_tokens: u32,
+    ) -> RatePermit<'_> {
+        let estimated_tokens = match self.limiter.limit.tokens_per_minute {
+            Some(_) => {
+                messages
+                    .iter()
+                    .map(|message| count_tokens(model, &message.content) as u64)
+                    .sum::<u64>()
+                    + u64::from(max_tokens)
+            }
+            None => 0,
+        };
+
+        self.limiter.acquire(estimated_tokens).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_limiting_requests_and_tokens_per_minute() {
+        let limiter = RateLimiter::new(RateLimit {
+            requests_per_minute: Some(2),
+            tokens_per_minute: Some(1_000),
+            max_concurrent: None,
+        });
+        let start = Instant::now();
+
+        let first = limiter.try_reserve(600, start).unwrap();
+        assert_eq!(
+            limiter.try_reserve(600, start + Duration::from_secs(10)),
+            Err(Duration::from_secs(50))
+        );
+
+        // Once the first request reports its real usage there is room again
+        limiter.settle(first, 300);
+        assert!(limiter
+            .try_reserve(600, start + Duration::from_secs(10))
+            .is_ok());
+        assert!(limiter
+            .try_reserve(1, start + Duration::from_secs(20))
+            .is_err());
+
+        // A minute later both have left the window
+        assert!(limiter
+            .try_reserve(1, start + Duration::from_secs(70))
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn tests_sharing_concurrency_slots() {
+        let limiter = RateLimiter::new(RateLimit {
+            max_concurrent: Some(1),
+            ..RateLimit::default()
+        });
+
+        let first = limiter.acquire(10).await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), limiter.acquire(10))
+                .await
+                .is_err()
+        );
+
+        first.settle(Usage::default());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), limiter.acquire(10))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn tests_freeing_tokens_of_failed_requests() {
+        let limiter = RateLimiter::new(RateLimit {
+            tokens_per_minute: Some(1_000),
+            ..RateLimit::default()
+        });
+
+        // Dropped without settling, as when the request errors
+        let failed = limiter.acquire(900).await;
+        drop(failed);
+        assert!(limiter.try_reserve(900, Instant::now()).is_ok());
+
+        // A settled request keeps its real usage
+        let settled = limiter.acquire(50).await;
+        settled.settle(Usage {
+            prompt_tokens: 60,
+            completion_tokens: 40,
+        });
+        assert_eq!(limiter.window.lock().unwrap().tokens(), 1_000);
+    }
+}
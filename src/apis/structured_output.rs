@@ -0,0 +1,169 @@
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use strum_macros::{Display, EnumString};
+
+use crate::models::general::llm::{
+    ChatOptions, JsonSchemaFormat, ResponseFormat, Tool, ToolChoice,
+};
+
+// Key that non-object outputs are wrapped in, since providers only constrain objects
+const WRAPPED_KEY: &str = "value";
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+#[serde(rename_all = "snake_case")]
+pub enum StructuredOutput {
+    // Rely on the prompt alone
+    Off,
+    // Ask for any JSON object
+    JsonObject,
+    // Ask for JSON matching the schema of the decoded type
+    JsonSchema,
+    // Have the model call a function whose parameters are the decoded type
+    Tool,
+}
+
+// Chat options constraining a completion to the JSON schema of T
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredRequest {
+    pub options: ChatOptions,
+    // Schema of the JSON the request asks for, wrapper included
+    schema: Value,
+    // The schema root is not an object, so the output sits under WRAPPED_KEY
+    wrapped: bool,
+}
+
+impl StructuredRequest {
+    // Named after the ai_function, which also names the schema or tool
+    pub fn new<T: JsonSchema>(mode: StructuredOutput, name: &str) -> Self {
+        let schema = schema_for::<T>();
+        let wrapped = schema["type"] != "object";
+
+        let parameters = if wrapped {
+            json!({
+                "type": "object",
+                "properties": { WRAPPED_KEY: schema },
+                "required": [WRAPPED_KEY]
+            })
+        } else {
+            schema
+        };
+
+        let options = match mode {
+            StructuredOutput::Off => ChatOptions::default(),
+            // The prompt never mentions the wrapper, so only objects can use plain JSON mode
+            StructuredOutput::JsonObject if wrapped => ChatOptions::default(),
+            StructuredOutput::JsonObject => ChatOptions {
+                response_format: Some(ResponseFormat::JsonObject),
+                ..ChatOptions::default()
+            },
+            StructuredOutput::JsonSchema => ChatOptions {
+                response_format: Some(ResponseFormat::JsonSchema {
+                    json_schema: JsonSchemaFormat {
+                        name: name.to_string(),
+                        schema: parameters.clone(),
+                        strict: false,
+                    },
+                }),
+                ..ChatOptions::default()
+            },
+            StructuredOutput::Tool => ChatOptions {
+                tools: Some(vec![Tool::function(
+                    name,
+                    &format!("Return the output of {}", name),
+                    parameters.clone(),
+                )]),
+                tool_choice: Some(ToolChoice::function(name)),
+                ..ChatOptions::default()
+            },
+        };
+
+        Self {
+            options,
+            schema: parameters,
+            wrapped,
+        }
+    }
+
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    // Providers that ignored the request answer unwrapped, so both shapes are accepted
+    pub fn decode<T: DeserializeOwned>(&self, output: &str) -> Result<T, serde_json::Error> {
+        if !self.wrapped {
+            return serde_json::from_str(output);
+        }
+
+        let mut value: Value = serde_json::from_str(output)?;
+        match value.get_mut(WRAPPED_KEY) {
+            Some(inner) => serde_json::from_value(inner.take()),
+            None => serde_json::from_value(value),
+        }
+    }
+}
+
+// Subschemas are inlined so the schema stays valid when wrapped
+fn schema_for<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator();
+
+    serde_json::to_value(generator.into_root_schema_for::<T>().schema)
+        .expect("JSON schemas are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::{ProjectScope, RouteObject};
+
+    #[test]
+    fn tests_requesting_object_schema() {
+        let request = StructuredRequest::new::<ProjectScope>(
+            StructuredOutput::JsonSchema,
+            "print_project_scope",
+        );
+
+        let Some(ResponseFormat::JsonSchema { json_schema }) = &request.options.response_format
+        else {
+            panic!("Expected a json_schema response format");
+        };
+        assert_eq!(json_schema.name, "print_project_scope");
+        assert!(json_schema.schema["properties"]["is_crud_required"].is_object());
+
+        let scope: ProjectScope = request
+            .decode(r#"{"is_crud_required": true, "is_user_login_and_logout": false, "is_external_urls_required": false}"#)
+            .unwrap();
+        assert!(scope.is_crud_required);
+    }
+
+    #[test]
+    fn tests_wrapping_array_in_tool_parameters() {
+        let request = StructuredRequest::new::<Vec<RouteObject>>(
+            StructuredOutput::Tool,
+            "print_rest_api_endpoints",
+        );
+
+        let tools = request.options.tools.as_ref().unwrap();
+        let parameters = &tools[0].function.parameters;
+        assert_eq!(parameters["properties"]["value"]["type"], "array");
+        assert_eq!(
+            request.options.tool_choice,
+            Some(ToolChoice::function("print_rest_api_endpoints"))
+        );
+
+        let wrapped: Vec<String> =
+            StructuredRequest::new::<Vec<String>>(StructuredOutput::Tool, "f")
+                .decode(r#"{"value": ["https://api.binance.com"]}"#)
+                .unwrap();
+        let bare: Vec<String> = StructuredRequest::new::<Vec<String>>(StructuredOutput::Tool, "f")
+            .decode(r#"["https://api.binance.com"]"#)
+            .unwrap();
+        assert_eq!(wrapped, bare);
+
+        let json_object = StructuredRequest::new::<Vec<String>>(StructuredOutput::JsonObject, "f");
+        assert_eq!(json_object.options, ChatOptions::default());
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use chrono::{SecondsFormat, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::general::llm::{ChatOptions, LlmResponse, Message, Usage};
+
+use super::{llm_config::LlmConfig, llm_error::LlmError};
+
+const REDACTED: &str = "[REDACTED]";
+
+// Key formats redacted whatever the config says, they end up in prompts,
+// generated code and error bodies
+pub const DEFAULT_SECRET_PATTERNS: [&str; 4] = [
+    // OpenAI and Anthropic API keys
+    r"sk-[A-Za-z0-9_-]{16,}",
+    r"(?i)bearer\s+[A-Za-z0-9._~+/-]{16,}=*",
+    // AWS access key ids
+    r"AKIA[0-9A-Z]{16}",
+    // GitHub tokens
+    r"gh[pousr]_[A-Za-z0-9]{36,}",
+];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscriptConfig {
+    pub enabled: bool,
+    // One <timestamp>-<pid>.jsonl file per run
+    pub dir: PathBuf,
+    // Regexes for further secrets, redacted along with the API key and common key formats
+    pub redact: Vec<String>,
+}
+
+impl Default for TranscriptConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("transcripts"),
+            redact: vec![],
+        }
+    }
+}
+
+// One attempt at a completion, as seen by the agent that asked for it
+pub struct Exchange<'a> {
+    pub agent_position: &'a str,
+    pub ai_function: &'a str,
+    pub model: &'a str,
+    pub messages: &'a [Message],
+    pub options: &'a ChatOptions,
+    pub result: &'a Result<LlmResponse, LlmError>,
+    pub latency: Duration,
+}
+
+#[derive(Serialize)]
+struct TranscriptRequest<'a> {
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "is_default")]
+    options: &'a ChatOptions,
+}
+
+fn is_default(options: &&ChatOptions) -> bool {
+    **options == ChatOptions::default()
+}
+
+// One line of the transcript
+#[derive(Serialize)]
+struct TranscriptEntry<'a> {
+    timestamp: String,
+    agent_position: &'a str,
+    ai_function: &'a str,
+    model: &'a str,
+    request: TranscriptRequest<'a>,
+    response: Option<&'a LlmResponse>,
+    latency_ms: u128,
+    usage: Option<Usage>,
+    error: Option<String>,
+}
+
+// Every exchange of a run as JSON lines, with secrets redacted.
+// The file is created with the first entry, so runs without calls leave none behind.
+#[derive(Debug)]
+pub struct Transcript {
+    // None when transcripts are off
+    path: Option<PathBuf>,
+    file: Mutex<Option<File>>,
+    // Exact values to redact, such as the configured API key
+    secrets: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Transcript {
+    pub fn new(config: &LlmConfig) -> Result<Self, LlmError> {
+        let patterns = DEFAULT_SECRET_PATTERNS
+            .iter()
+            .copied()
+            .chain(config.transcript.redact.iter().map(String::as_str))
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    LlmError::ConfigInvalid(format!("transcript redact pattern {}: {}", pattern, e))
+                })
+            })
+            .collect::<Result<Vec<Regex>, LlmError>>()?;
+
+        let path = config.transcript.enabled.then(|| {
+            config.transcript.dir.join(format!(
+                "{}-{}.jsonl",
+                Utc::now().format("%Y%m%dT%H%M%SZ"),
+                std::process::id()
+            ))
+        });
+
+        Ok(Self {
+            path,
+            file: Mutex::new(None),
+            secrets: config.api_key.iter().cloned().collect(),
+            patterns,
+        })
+    }
+
+    // Where the transcript is written once something was logged
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref().filter(|path| path.exists())
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = self
+            .secrets
+            .iter()
+            .filter(|secret| !secret.is_empty())
+            .fold(text.to_string(), |text, secret| {
+                text.replace(secret, REDACTED)
+            });
+
+        for pattern in &self.patterns {
+            text = pattern.replace_all(&text, REDACTED).into_owned();
+        }
+        text
+    }
+
+    // Strings are redacted one by one so a pattern can never break the JSON around them
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact(text),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_value(value)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|value| self.redact_value(value)),
+            _ => {}
+        }
+    }
+
+    pub fn log(&self, exchange: Exchange<'_>) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let (response, usage, error) = match exchange.result {
+            Ok(response) => (Some(response), Some(response.usage), None),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+
+        let entry = TranscriptEntry {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            agent_position: exchange.agent_position,
+            ai_function: exchange.ai_function,
+            model: exchange.model,
+            request: TranscriptRequest {
+                messages: exchange.messages,
+                options: exchange.options,
+            },
+            response,
+            latency_ms: exchange.latency.as_millis(),
+            usage,
+            error,
+        };
+
+        let mut line = serde_json::to_value(&entry)?;
+        self.redact_value(&mut line);
+
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            *file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+
+        let file = file.as_mut().expect("Transcript file was just opened");
+        writeln!(file, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::llm_client::LlmProvider;
+    use std::env;
+
+    #[test]
+    fn tests_logging_redacted_exchanges() {
+        let dir = env::temp_dir().join(format!("transcripts_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut config = LlmConfig::for_provider(LlmProvider::OpenAi);
+        config.api_key = Some("my-org-key-1234".to_string());
+        config.transcript = TranscriptConfig {
+            enabled: true,
+            dir: dir.clone(),
+            redact: vec![r"postgres://\S+".to_string()],
+        };
+        let transcript = Transcript::new(&config).unwrap();
+        assert_eq!(transcript.path(), None);
+
+        let messages = [Message {
+            role: "system".to_string(),
+            content: "Connect to postgres://admin:hunter2@db/app with key my-org-key-1234"
+                .to_string(),
+        }];
+        let ok = Ok(LlmResponse {
+            content: "let key = \"sk-proj-abcdefghijklmnopqrstuvwx\";".to_string(),
+            usage: Usage {
+                prompt_tokens: 12,
+                completion_tokens: 8,
+            },
+            tool_calls: vec![],
+        });
+        let failed = Err(LlmError::from_status(
+            401,
+            r#"{"error": {"message": "Incorrect API key provided: sk-abcdefghijklmnopqrstuvwx"}}"#
+                .to_string(),
+            None,
+        ));
+
+        for result in [&ok, &failed] {
+            transcript
+                .log(Exchange {
+                    agent_position: "Backend Developer",
+                    ai_function: "print_backend_webserver_code",
+                    model: "gpt-4o",
+                    messages: &messages,
+                    options: &ChatOptions::default(),
+                    result,
+                    latency: Duration::from_millis(1500),
+                })
+                .unwrap();
+        }
+
+        let contents = fs::read_to_string(transcript.path().unwrap()).unwrap();
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["agent_position"], "Backend Developer");
+        assert_eq!(lines[0]["ai_function"], "print_backend_webserver_code");
+        assert_eq!(lines[0]["latency_ms"], 1500);
+        assert_eq!(lines[0]["usage"]["completion_tokens"], 8);
+        assert_eq!(
+            lines[0]["request"]["messages"][0]["content"],
+            "Connect to [REDACTED] with key [REDACTED]"
+        );
+        assert_eq!(lines[0]["response"]["content"], "let key = \"[REDACTED]\";");
+        assert!(lines[0]["error"].is_null());
+        assert!(lines[1]["response"].is_null());
+        assert_eq!(
+            lines[1]["error"],
+            "LLM API returned 401: Incorrect API key provided: [REDACTED]"
+        );
+        assert!(!contents.contains("hunter2"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
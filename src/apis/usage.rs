@@ -0,0 +1,292 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::AddAssign,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::models::general::llm::Usage;
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u32 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl Default for PriceTable {
+    // List prices at the time of writing, override them in llm.toml when they change
+    fn default() -> Self {
+        let prices = [
+            ("gpt-4-turbo", 10.0, 30.0),
+            ("gpt-4o", 5.0, 15.0),
+            ("gpt-4o-mini", 0.15, 0.6),
+            ("gpt-4", 30.0, 60.0),
+            ("gpt-3.5-turbo", 0.5, 1.5),
+            ("claude-3-opus", 15.0, 75.0),
+            ("claude-3-sonnet", 3.0, 15.0),
+            ("claude-3-5-sonnet", 3.0, 15.0),
+            ("claude-3-haiku", 0.25, 1.25),
+        ]
+        .into_iter()
+        .map(|(model, input, output)| {
+            (
+                model.to_string(),
+                ModelPrice {
+                    input_per_million: input,
+                    output_per_million: output,
+                },
+            )
+        })
+        .collect();
+
+        Self { prices }
+    }
+}
+
+impl PriceTable {
+    pub fn extend(&mut self, overrides: HashMap<String, ModelPrice>) {
+        self.prices.extend(overrides);
+    }
+
+    // Exact match first, then the longest prefix so dated snapshots share a price
+    pub fn lookup(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.prices.get(model) {
+            return Some(*price);
+        }
+
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    pub agent_position: String,
+    pub ai_function: String,
+    pub model: String,
+    pub usage: Usage,
+}
+
+// Every completion of a run, shared by all agents using the same client
+#[derive(Debug)]
+pub struct UsageLedger {
+    records: Mutex<Vec<UsageRecord>>,
+    started: Instant,
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        Self {
+            records: Mutex::new(Vec::new()),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl UsageLedger {
+    pub fn total(&self) -> Usage {
+        let mut total = Usage::default();
+        for record in self.records.lock().unwrap().iter() {
+            total += record.usage;
+        }
+        total
+    }
+
+    // Wall-clock time since the run started
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn record(&self, agent_position: &str, ai_function: &str, model: &str, usage: Usage) {
+        self.records.lock().unwrap().push(UsageRecord {
+            agent_position: agent_position.to_string(),
+            ai_function: ai_function.to_string(),
+            model: model.to_string(),
+            usage,
+        });
+    }
+
+    // Group usage by agent and ai_function, in the order they were first called
+    pub fn report(&self, prices: &PriceTable) -> UsageReport {
+        let mut report = UsageReport::default();
+
+        for record in self.records.lock().unwrap().iter() {
+            let cost = prices.lookup(&record.model).map(|p| p.cost(record.usage));
+
+            if cost.is_none() && !report.unpriced_models.contains(&record.model) {
+                report.unpriced_models.push(record.model.clone());
+            }
+
+            let position = report.rows.iter().position(|row| {
+                row.agent_position == record.agent_position && row.ai_function == record.ai_function
+            });
+
+            let row = match position {
+                Some(index) => &mut report.rows[index],
+                None => {
+                    report.rows.push(UsageReportRow {
+                        agent_position: record.agent_position.clone(),
+                        ai_function: record.ai_function.clone(),
+                        ..UsageReportRow::default()
+                    });
+                    report.rows.last_mut().unwrap()
+                }
+            };
+
+            row.calls += 1;
+            row.usage += record.usage;
+            row.cost += cost.unwrap_or(0.0);
+
+            report.total += record.usage;
+            report.total_cost += cost.unwrap_or(0.0);
+        }
+
+        report
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UsageReportRow {
+    pub agent_position: String,
+    pub ai_function: String,
+    pub calls: u32,
+    pub usage: Usage,
+    pub cost: f64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UsageReport {
+    pub rows: Vec<UsageReportRow>,
+    pub total: Usage,
+    pub total_cost: f64,
+    // Models missing from the price table, their tokens are counted but not costed
+    pub unpriced_models: Vec<String>,
+}
+
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<22} {:<32} {:>5} {:>10} {:>10} {:>10}",
+            "Agent", "AI Function", "Calls", "Prompt", "Completion", "Cost"
+        )?;
+
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:<22} {:<32} {:>5} {:>10} {:>10} {:>10}",
+                row.agent_position,
+                row.ai_function,
+                row.calls,
+                row.usage.prompt_tokens,
+                row.usage.completion_tokens,
+                format!("${:.4}", row.cost)
+            )?;
+        }
+
+        write!(
+            f,
+            "{:<22} {:<32} {:>5} {:>10} {:>10} {:>10}",
+            "Total",
+            "",
+            self.rows.iter().map(|row| row.calls).sum::<u32>(),
+            self.total.prompt_tokens,
+            self.total.completion_tokens,
+            format!("${:.4}", self.total_cost)
+        )?;
+
+        if !self.unpriced_models.is_empty() {
+            write!(
+                f,
+                "\nNo price configured for: {}",
+                self.unpriced_models.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_looking_up_prices_by_prefix() {
+        let prices = PriceTable::default();
+
+        let turbo = prices.lookup("gpt-4-turbo-2024-04-09").unwrap();
+        assert_eq!(turbo.input_per_million, 10.0);
+
+        let mini = prices.lookup("gpt-4o-mini").unwrap();
+        assert_eq!(mini.output_per_million, 0.6);
+
+        assert!(prices.lookup("llama3").is_none());
+    }
+
+    #[test]
+    fn tests_reporting_usage_per_agent_and_function() {
+        let ledger = UsageLedger::default();
+        let usage = Usage {
+            prompt_tokens: 1_000,
+            completion_tokens: 500,
+        };
+
+        ledger.record(
+            "Solutions Architect",
+            "print_project_scope",
+            "gpt-4-turbo",
+            usage,
+        );
+        ledger.record(
+            "Solutions Architect",
+            "print_project_scope",
+            "gpt-4-turbo",
+            usage,
+        );
+        ledger.record("Backend Developer", "print_fixed_code", "llama3", usage);
+
+        let report = ledger.report(&PriceTable::default());
+
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(report.rows[0].calls, 2);
+        assert_eq!(report.total.prompt_tokens, 3_000);
+        assert_eq!(report.total.completion_tokens, 1_500);
+        assert!((report.total_cost - 0.05).abs() < 1e-9);
+        assert_eq!(report.unpriced_models, vec!["llama3".to_string()]);
+    }
+}
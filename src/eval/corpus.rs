@@ -0,0 +1,182 @@
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use serde::Deserialize;
+use strum_macros::{Display, EnumString};
+
+use crate::apis::llm_error::LlmError;
+
+// Used when no path is given on the command line
+pub const DEFAULT_EVAL_PATH: &str = "eval.toml";
+
+// Pipeline stages in the order they run, each building on the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display, Deserialize)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum EvalStage {
+    // convert_user_input_to_goal, otherwise the request is the project description
+    Goal,
+    // Project scope and external urls
+    Architect,
+    // Code generation, build, endpoint extraction and endpoint checks
+    Backend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseSource {
+    // Serve each run's cassette, nothing is sent to the provider
+    Replay,
+    // Call the configured provider and write the cassettes to replay later
+    Record,
+    // Call the configured provider, e.g. a mock server, and keep nothing
+    Live,
+}
+
+// One set of prompts to compare, built-in unless a prompts directory is given
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalVariant {
+    pub name: String,
+    #[serde(default)]
+    pub prompts: Option<PathBuf>,
+}
+
+// A project request from the corpus
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalCase {
+    pub name: String,
+    pub request: String,
+}
+
+// Every case is run once per variant through the chosen stages
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvalConfig {
+    pub stages: Vec<EvalStage>,
+    pub responses: ResponseSource,
+    // Cassettes are kept at <cassette_dir>/<variant>/<case>.json
+    pub cassette_dir: PathBuf,
+    // Markdown comparison table
+    pub report: PathBuf,
+    pub variants: Vec<EvalVariant>,
+    pub cases: Vec<EvalCase>,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self {
+            stages: vec![EvalStage::Goal, EvalStage::Architect, EvalStage::Backend],
+            responses: ResponseSource::Replay,
+            cassette_dir: PathBuf::from("eval/cassettes"),
+            report: PathBuf::from("eval/report.md"),
+            variants: vec![],
+            cases: vec![],
+        }
+    }
+}
+
+impl EvalConfig {
+    pub fn load(path: &str) -> Result<Self, LlmError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| LlmError::ConfigMissing(format!("eval corpus {}: {}", path, e)))?;
+
+        Self::parse(&contents)
+            .map_err(|reason| LlmError::ConfigInvalid(format!("eval corpus {}: {}", path, reason)))
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut eval: Self = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        if eval.variants.is_empty() {
+            eval.variants.push(EvalVariant {
+                name: "built-in".to_string(),
+                prompts: None,
+            });
+        }
+        eval.stages.sort();
+        eval.stages.dedup();
+
+        if eval.cases.is_empty() {
+            return Err("no cases to run".to_string());
+        }
+
+        // Names become cassette paths, so keep them unique and file name safe
+        let variant_names = eval.variants.iter().map(|variant| &variant.name);
+        let case_names = eval.cases.iter().map(|case| &case.name);
+        for (kind, names) in [
+            ("variant", variant_names.collect::<Vec<_>>()),
+            ("case", case_names.collect::<Vec<_>>()),
+        ] {
+            let mut seen = HashSet::new();
+            for name in names {
+                let is_safe = !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+                if !is_safe || name.starts_with('.') {
+                    return Err(format!(
+                        "{} name {:?} may only use letters, digits, '-', '_' and '.'",
+                        kind, name
+                    ));
+                }
+                if !seen.insert(name) {
+                    return Err(format!("{} {} is listed twice", kind, name));
+                }
+            }
+        }
+
+        Ok(eval)
+    }
+
+    pub fn cassette_path(&self, variant: &EvalVariant, case: &EvalCase) -> PathBuf {
+        self.cassette_dir
+            .join(&variant.name)
+            .join(format!("{}.json", case.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parsing_eval_corpus() {
+        let eval = EvalConfig::parse(
+            r#"
+            stages = ["backend", "architect"]
+            responses = "record"
+
+            [[variants]]
+            name = "strict-endpoints"
+            prompts = "eval/prompts/strict"
+
+            [[cases]]
+            name = "todo"
+            request = "a todo list with user login"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(eval.stages, [EvalStage::Architect, EvalStage::Backend]);
+        assert_eq!(eval.responses, ResponseSource::Record);
+        assert_eq!(
+            eval.cassette_path(&eval.variants[0], &eval.cases[0]),
+            PathBuf::from("eval/cassettes/strict-endpoints/todo.json")
+        );
+
+        // Without variants the built-in prompts are evaluated
+        let eval = EvalConfig::parse("[[cases]]\nname = \"todo\"\nrequest = \"todo\"").unwrap();
+        assert_eq!(eval.variants[0].name, "built-in");
+        assert_eq!(eval.variants[0].prompts, None);
+
+        for invalid in [
+            "stages = [\"goal\"]",
+            "[[cases]]\nname = \"../todo\"\nrequest = \"todo\"",
+            "[[cases]]\nname = \"todo\"\nrequest = \"a\"\n[[cases]]\nname = \"todo\"\nrequest = \"b\"",
+        ] {
+            assert!(EvalConfig::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
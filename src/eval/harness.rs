@@ -0,0 +1,273 @@
+use std::{error::Error, sync::Arc};
+
+use crate::{
+    ai_functions::aifunc_managing::convert_user_input_to_goal,
+    apis::{
+        cassette::{CassetteConfig, CassetteMode},
+        llm_client::{create_llm_client, LlmClient},
+        llm_config::LlmConfig,
+        llm_error::LlmError,
+        prompts::{PromptRegistry, PromptSource},
+        response_cache::CacheMode,
+    },
+    helpers::{command_line::PrintCommand, generals::ai_task_request, workspace::Workspace},
+    models::{
+        agent_basic::{basic_agent::BasicAgent, basic_traits::BasicTrait},
+        agents::{
+            agent_architect::AgentSolutionArchitect,
+            agent_backend::{AgentBackendDeveloper, BackendTestReport},
+            agent_traits::{FactSheet, SpecialFunctions},
+        },
+    },
+};
+
+use super::{
+    corpus::{EvalCase, EvalConfig, EvalStage, EvalVariant, ResponseSource},
+    scoring::{CaseScore, EvalReport, VariantInfo},
+};
+
+const POSITION: &str = "Eval Harness";
+
+// Run every case with every variant's prompts. A failing case is scored and
+// the run goes on, only a variant's unusable prompts stop it.
+pub async fn run_eval(
+    eval: &EvalConfig,
+    base: &LlmConfig,
+    workspace: &Workspace,
+) -> Result<EvalReport, LlmError> {
+    let mut report = EvalReport::default();
+
+    for variant in &eval.variants {
+        let mut prompts = PromptRegistry::default();
+        prompts.dir = variant.prompts.clone();
+        prompts.load()?;
+
+        report.variants.push(VariantInfo {
+            name: variant.name.clone(),
+            prompt_versions: prompts
+                .list()
+                .into_iter()
+                .filter_map(|(ai_function, source)| match source {
+                    PromptSource::File { version, .. } => {
+                        Some(format!("{}@{}", ai_function, version))
+                    }
+                    PromptSource::BuiltIn => None,
+                })
+                .collect(),
+        });
+
+        for case in &eval.cases {
+            PrintCommand::AICall.print_agent_message(
+                POSITION,
+                &format!("Running {} with {} prompts", case.name, variant.name),
+            );
+
+            let mut config = base.clone();
+            config.prompts = prompts.clone();
+            // Every run has to reach the provider or its own cassette
+            config.cache.mode = CacheMode::Off;
+            config.cassette = CassetteConfig {
+                mode: match eval.responses {
+                    ResponseSource::Replay => CassetteMode::Replay,
+                    ResponseSource::Record => CassetteMode::Record,
+                    ResponseSource::Live => CassetteMode::Off,
+                },
+                path: eval.cassette_path(variant, case),
+            };
+
+            report
+                .cases
+                .push(run_case(eval, variant, case, config, workspace).await);
+        }
+    }
+
+    Ok(report)
+}
+
+async fn run_case(
+    eval: &EvalConfig,
+    variant: &EvalVariant,
+    case: &EvalCase,
+    config: LlmConfig,
+    workspace: &Workspace,
+) -> CaseScore {
+    let mut score = CaseScore {
+        variant: variant.name.clone(),
+        case: case.name.clone(),
+        ..CaseScore::default()
+    };
+
+    let llm = match create_llm_client(config) {
+        Ok(llm) => llm,
+        Err(e) => {
+            score.error = Some(e.to_string());
+            return score;
+        }
+    };
+
+    let result = run_stages(
+        &eval.stages,
+        llm.clone(),
+        case,
+        workspace,
+        &mut score.backend,
+    )
+    .await;
+
+    let failed_to_decode = matches!(
+        result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<LlmError>()),
+        Some(LlmError::Decode { .. })
+    );
+    score.count_json_replies(&llm.usage().report(&llm.config().prices), failed_to_decode);
+    score.error = result.err().map(|e| e.to_string());
+
+    if let Some(error) = &score.error {
+        PrintCommand::Issue.print_agent_message(
+            POSITION,
+            &format!("{} with {} failed: {}", case.name, variant.name, error),
+        );
+    }
+
+    score
+}
+
+// The same agents a real run uses, stopping at the first stage that fails
+async fn run_stages(
+    stages: &[EvalStage],
+    llm: Arc<dyn LlmClient>,
+    case: &EvalCase,
+    workspace: &Workspace,
+    backend_report: &mut Option<BackendTestReport>,
+) -> Result<(), Box<dyn Error>> {
+    let mut factsheet = FactSheet {
+        project_description: case.request.clone(),
+        ..Default::default()
+    };
+
+    for stage in stages {
+        match stage {
+            EvalStage::Goal => {
+                let mut manager = BasicAgent::new(
+                    "Turns the case request into a project description".to_string(),
+                    "Project Manager".to_string(),
+                );
+
+                factsheet.project_description = ai_task_request(
+                    llm.as_ref(),
+                    case.request.clone(),
+                    &mut manager,
+                    get_function_string!(convert_user_input_to_goal),
+                    convert_user_input_to_goal,
+                    false,
+                )
+                .await?;
+            }
+            EvalStage::Architect => {
+                AgentSolutionArchitect::new(llm.clone())
+                    .execute(&mut factsheet)
+                    .await?
+            }
+            EvalStage::Backend => {
+                let mut agent = AgentBackendDeveloper::new(llm.clone(), workspace.clone());
+                let result = agent.execute(&mut factsheet).await;
+
+                // Scored even when the backend gave up, a failed build is a result too
+                *backend_report = Some(agent.test_report().clone());
+                result?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::{
+        llm_client::LlmProvider,
+        mock_llm::{MockLlmServer, MockScript},
+    };
+    use std::{env, fs, path::PathBuf};
+
+    #[tokio::test]
+    async fn tests_recording_and_replaying_variants() {
+        let root = env::temp_dir().join(format!("eval_harness_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let prompts_dir = root.join("prompts");
+        fs::create_dir_all(&prompts_dir).unwrap();
+        fs::write(
+            prompts_dir.join("print_project_scope.toml"),
+            r#"
+            version = "2"
+            template = "pub fn print_project_scope(_project_description: &str) { /// Print the ProjectScope JSON for {{input}} }"
+            "#,
+        )
+        .unwrap();
+
+        let scope = r#"{"is_crud_required": true, "is_user_login_and_logout": false, "is_external_urls_required": false}"#;
+        let script = MockScript::new()
+            .reply("convert_user_input_to_goal", "build a todo list backend")
+            .reply("print_project_scope", "Sure! {\"is_crud_required\": tru")
+            .reply("print_project_scope", scope)
+            .reply("print_repaired_json", scope);
+
+        let server = MockLlmServer::start(script).await;
+        let mut eval = EvalConfig {
+            stages: vec![EvalStage::Goal, EvalStage::Architect],
+            responses: ResponseSource::Record,
+            cassette_dir: root.join("cassettes"),
+            report: root.join("report.md"),
+            variants: vec![
+                EvalVariant {
+                    name: "built-in".to_string(),
+                    prompts: None,
+                },
+                EvalVariant {
+                    name: "v2".to_string(),
+                    prompts: Some(prompts_dir),
+                },
+            ],
+            cases: vec![EvalCase {
+                name: "todo".to_string(),
+                request: "a todo app".to_string(),
+            }],
+        };
+        let workspace = Workspace::new(PathBuf::from("unused"));
+
+        let recorded = run_eval(&eval, &server.config(), &workspace).await.unwrap();
+        server.stop().await;
+
+        // The built-in prompts got cut-off JSON back that needed a repair
+        assert_eq!(
+            recorded.variants[1].prompt_versions,
+            ["print_project_scope@2"]
+        );
+        let scores: Vec<(u32, u32, Option<String>)> = recorded
+            .cases
+            .iter()
+            .map(|score| (score.json_replies, score.json_failures, score.error.clone()))
+            .collect();
+        assert_eq!(scores, [(2, 1, None), (1, 0, None)]);
+        assert!(recorded.cases[0].tokens > 0);
+        assert!(eval
+            .cassette_path(&eval.variants[1], &eval.cases[0])
+            .exists());
+
+        // Replaying needs no server and scores the same
+        eval.responses = ResponseSource::Replay;
+        let replayed = run_eval(
+            &eval,
+            &LlmConfig::for_provider(LlmProvider::OpenAi),
+            &workspace,
+        )
+        .await
+        .unwrap();
+        assert_eq!(replayed, recorded);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
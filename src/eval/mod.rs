@@ -0,0 +1,3 @@
+pub mod corpus;
+pub mod harness;
+pub mod scoring;
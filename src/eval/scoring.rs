@@ -0,0 +1,269 @@
+use std::fmt;
+
+use crate::{apis::usage::UsageReport, models::agents::agent_backend::BackendTestReport};
+
+// ai_functions whose replies are decoded as JSON
+const DECODED_AI_FUNCTIONS: [&str; 4] = [
+    get_function_string!(print_project_scope),
+    get_function_string!(print_site_urls),
+    get_function_string!(print_data_model),
+    get_function_string!(print_rest_api_endpoints),
+];
+
+// How one case did with one variant's prompts
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CaseScore {
+    pub variant: String,
+    pub case: String,
+    // JSON replies received, repair replies included, and how many failed to decode
+    pub json_replies: u32,
+    pub json_failures: u32,
+    // None when the backend stage was not run
+    pub backend: Option<BackendTestReport>,
+    pub tokens: u32,
+    pub error: Option<String>,
+}
+
+impl CaseScore {
+    // Every invalid JSON reply is either sent back for repair or ends the stage with
+    // a decode error, so the repair calls plus a final failure count the invalid ones
+    pub fn count_json_replies(&mut self, usage: &UsageReport, failed_to_decode: bool) {
+        let calls = |ai_functions: &[&str]| -> u32 {
+            usage
+                .rows
+                .iter()
+                .filter(|row| ai_functions.contains(&row.ai_function.as_str()))
+                .map(|row| row.calls)
+                .sum()
+        };
+
+        let repairs = calls(&[get_function_string!(print_repaired_json)]);
+        self.json_replies = calls(&DECODED_AI_FUNCTIONS) + repairs;
+        self.json_failures = repairs + u32::from(failed_to_decode);
+        self.tokens = usage.total.total_tokens();
+    }
+
+    // Builds attempted, one more than the fixes the backend needed
+    pub fn iterations(&self) -> Option<u32> {
+        self.backend
+            .as_ref()
+            .map(|backend| backend.build_failures + u32::from(backend.built))
+    }
+}
+
+// One variant and the prompt versions it overrides
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantInfo {
+    pub name: String,
+    // ai_function@version for each template, empty when all prompts are built-in
+    pub prompt_versions: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EvalReport {
+    pub variants: Vec<VariantInfo>,
+    pub cases: Vec<CaseScore>,
+}
+
+impl EvalReport {
+    fn variant_cases<'a>(&'a self, variant: &'a str) -> impl Iterator<Item = &'a CaseScore> {
+        self.cases
+            .iter()
+            .filter(move |score| score.variant == variant)
+    }
+}
+
+// "passed/total (percent)", or "-" when there was nothing to count
+fn ratio(passed: u32, total: u32) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!(
+        "{}/{} ({:.0}%)",
+        passed,
+        total,
+        f64::from(passed) * 100.0 / f64::from(total)
+    )
+}
+
+// Keep errors on one table row
+fn table_cell(text: &str) -> String {
+    text.lines().next().unwrap_or_default().replace('|', "\\|")
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "## Variants\n")?;
+        writeln!(
+            f,
+            "| variant | prompts | cases | failed | json decode | build | endpoints | tokens/case | builds/case |"
+        )?;
+        writeln!(f, "|---|---|---|---|---|---|---|---|---|")?;
+
+        for variant in &self.variants {
+            let cases: Vec<&CaseScore> = self.variant_cases(&variant.name).collect();
+            let backends: Vec<&BackendTestReport> = cases
+                .iter()
+                .filter_map(|score| score.backend.as_ref())
+                .collect();
+            let sum = |count: fn(&CaseScore) -> u32| -> u32 {
+                cases.iter().map(|score| count(score)).sum()
+            };
+
+            let prompts = match variant.prompt_versions.is_empty() {
+                true => "built-in".to_string(),
+                false => variant.prompt_versions.join(", "),
+            };
+            let per_case = |total: u32| match cases.len() {
+                0 => "-".to_string(),
+                n => format!("{:.1}", f64::from(total) / n as f64),
+            };
+
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                variant.name,
+                prompts,
+                cases.len(),
+                cases.iter().filter(|score| score.error.is_some()).count(),
+                ratio(
+                    sum(|score| score.json_replies - score.json_failures),
+                    sum(|score| score.json_replies)
+                ),
+                ratio(
+                    backends.iter().filter(|backend| backend.built).count() as u32,
+                    backends.len() as u32
+                ),
+                ratio(
+                    backends
+                        .iter()
+                        .map(|backend| backend.endpoints_passed)
+                        .sum(),
+                    backends
+                        .iter()
+                        .map(|backend| backend.endpoints_checked)
+                        .sum()
+                ),
+                per_case(sum(|score| score.tokens)),
+                match backends.is_empty() {
+                    true => "-".to_string(),
+                    false => per_case(sum(|score| score.iterations().unwrap_or(0))),
+                },
+            )?;
+        }
+
+        writeln!(f, "\n## Cases\n")?;
+        writeln!(
+            f,
+            "| variant | case | json decode | build | endpoints | tokens | builds | error |"
+        )?;
+        writeln!(f, "|---|---|---|---|---|---|---|---|")?;
+
+        for score in &self.cases {
+            let (build, endpoints) = match &score.backend {
+                Some(backend) => (
+                    if backend.built { "ok" } else { "failed" }.to_string(),
+                    ratio(backend.endpoints_passed, backend.endpoints_checked),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {} | {} | {} |",
+                score.variant,
+                score.case,
+                ratio(score.json_replies - score.json_failures, score.json_replies),
+                build,
+                endpoints,
+                score.tokens,
+                score
+                    .iterations()
+                    .map_or("-".to_string(), |builds| builds.to_string()),
+                score.error.as_deref().map(table_cell).unwrap_or_default(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apis::usage::UsageReportRow, models::general::llm::Usage};
+
+    #[test]
+    fn tests_scoring_and_comparing_variants() {
+        let row = |ai_function: &str, calls: u32| UsageReportRow {
+            ai_function: ai_function.to_string(),
+            calls,
+            ..UsageReportRow::default()
+        };
+        let usage = UsageReport {
+            rows: vec![
+                row("convert_user_input_to_goal", 1),
+                row("print_project_scope", 1),
+                row("print_repaired_json", 2),
+                row("print_rest_api_endpoints", 1),
+            ],
+            total: Usage {
+                prompt_tokens: 900,
+                completion_tokens: 300,
+            },
+            ..UsageReport::default()
+        };
+
+        let mut repaired = CaseScore {
+            variant: "built-in".to_string(),
+            case: "todo".to_string(),
+            backend: Some(BackendTestReport {
+                build_failures: 1,
+                built: true,
+                endpoints_checked: 4,
+                endpoints_passed: 3,
+            }),
+            ..CaseScore::default()
+        };
+        repaired.count_json_replies(&usage, false);
+
+        assert_eq!(repaired.json_replies, 4);
+        assert_eq!(repaired.json_failures, 2);
+        assert_eq!(repaired.tokens, 1200);
+        assert_eq!(repaired.iterations(), Some(2));
+
+        let report = EvalReport {
+            variants: vec![
+                VariantInfo {
+                    name: "built-in".to_string(),
+                    prompt_versions: vec![],
+                },
+                VariantInfo {
+                    name: "strict".to_string(),
+                    prompt_versions: vec!["print_rest_api_endpoints@2".to_string()],
+                },
+            ],
+            cases: vec![
+                repaired,
+                CaseScore {
+                    variant: "strict".to_string(),
+                    case: "todo".to_string(),
+                    json_replies: 2,
+                    json_failures: 1,
+                    tokens: 800,
+                    error: Some("Failed to decode LLM response: EOF | line 1".to_string()),
+                    ..CaseScore::default()
+                },
+            ],
+        };
+        let table = report.to_string();
+
+        assert!(table.contains(
+            "| built-in | built-in | 1 | 0 | 2/4 (50%) | 1/1 (100%) | 3/4 (75%) | 1200.0 | 2.0 |"
+        ));
+        assert!(table.contains(
+            "| strict | print_rest_api_endpoints@2 | 1 | 1 | 1/2 (50%) | - | - | 800.0 | - |"
+        ));
+        assert!(table.contains("| strict | todo | 1/2 (50%) | - | - | 800 | - | Failed to decode LLM response: EOF \\| line 1 |"));
+    }
+}
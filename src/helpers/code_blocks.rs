@@ -0,0 +1,160 @@
+// Pulls source code out of LLM replies that wrap it in markdown fences and commentary
+
+const RUST_TAGS: [&str; 2] = ["rust", "rs"];
+
+#[derive(Debug, PartialEq)]
+struct CodeBlock {
+    tag: String,
+    code: String,
+}
+
+// The code to save, and what was removed to get it if anything
+#[derive(Debug, PartialEq)]
+pub struct ExtractedCode {
+    pub code: String,
+    pub warning: Option<String>,
+}
+
+// Choose the Rust block of a reply, dropping fences and the prose around them.
+// Replies without fences are taken as plain code.
+pub fn extract_rust_code(reply: &str) -> ExtractedCode {
+    let (blocks, prose_lines) = parse_blocks(reply);
+
+    if blocks.is_empty() {
+        return ExtractedCode {
+            code: reply.to_string(),
+            warning: None,
+        };
+    }
+
+    // Prefer blocks tagged as Rust, then untagged ones. Models sometimes add a
+    // Cargo.toml or a usage snippet, so the longest candidate is the program.
+    let is_rust = |block: &&CodeBlock| RUST_TAGS.contains(&block.tag.as_str());
+    let is_untagged = |block: &&CodeBlock| block.tag.is_empty();
+
+    let chosen = longest(blocks.iter().filter(is_rust))
+        .or_else(|| longest(blocks.iter().filter(is_untagged)))
+        .or_else(|| longest(blocks.iter()))
+        .expect("There is at least one block");
+
+    let mut removed = vec![];
+    if blocks.len() > 1 {
+        removed.push(format!("{} other code blocks", blocks.len() - 1));
+    }
+    if prose_lines > 0 {
+        removed.push(format!("{} lines of commentary", prose_lines));
+    }
+
+    let warning = if removed.is_empty() {
+        "Removed markdown code fences from the generated code".to_string()
+    } else {
+        format!(
+            "Removed markdown code fences and {} from the generated code",
+            removed.join(" and ")
+        )
+    };
+
+    ExtractedCode {
+        code: format!("{}\n", chosen.code.trim_end()),
+        warning: Some(warning),
+    }
+}
+
+// The longest block tagged with one of the given languages, for replies that
+// hold several files in fences of their own
+pub fn extract_tagged_code(reply: &str, tags: &[&str]) -> Option<String> {
+    let (blocks, _) = parse_blocks(reply);
+
+    longest(
+        blocks
+            .iter()
+            .filter(|block| tags.contains(&block.tag.as_str())),
+    )
+    .map(|block| format!("{}\n", block.code.trim_end()))
+}
+
+fn longest<'a>(blocks: impl Iterator<Item = &'a CodeBlock>) -> Option<&'a CodeBlock> {
+    blocks.max_by_key(|block| block.code.len())
+}
+
+// Fenced blocks in order, and the number of non-empty lines outside of them.
+// A fence left open at the end of a cut-off reply runs to the end.
+fn parse_blocks(reply: &str) -> (Vec<CodeBlock>, usize) {
+    let mut blocks = vec![];
+    let mut prose_lines = 0;
+    let mut current: Option<CodeBlock> = None;
+
+    for line in reply.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+
+        match (&mut current, fence) {
+            (Some(_), Some(_)) => blocks.extend(current.take()),
+            (Some(block), None) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+            }
+            (None, Some(tag)) => {
+                current = Some(CodeBlock {
+                    tag: tag.trim().to_lowercase(),
+                    code: String::new(),
+                })
+            }
+            (None, None) if !line.trim().is_empty() => prose_lines += 1,
+            (None, None) => {}
+        }
+    }
+
+    blocks.extend(current);
+    (blocks, prose_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_keeping_unfenced_code() {
+        let reply = "use actix_web::App;\n\nfn main() {}\n";
+
+        assert_eq!(
+            extract_rust_code(reply),
+            ExtractedCode {
+                code: reply.to_string(),
+                warning: None,
+            }
+        );
+    }
+
+    #[test]
+    fn tests_choosing_rust_block_over_prose_and_toml() {
+        let reply = "Here is the improved server:\n\n```toml\n[dependencies]\nactix-web = \"4\"\n```\n\n```rust\nuse actix_web::App;\n\nfn main() {}\n```\n\nRun it with cargo run.";
+
+        let extracted = extract_rust_code(reply);
+
+        assert_eq!(extracted.code, "use actix_web::App;\n\nfn main() {}\n");
+        assert_eq!(
+            extracted.warning.unwrap(),
+            "Removed markdown code fences and 1 other code blocks and 2 lines of commentary from the generated code"
+        );
+    }
+
+    #[test]
+    fn tests_extracting_blocks_by_tag() {
+        let reply = "```html\n<script src=\"app.js\"></script>\n```\nThe script:\n```js\nfetch(\"/items\");\n```\n```css\nbody {}";
+
+        assert_eq!(
+            extract_tagged_code(reply, &["javascript", "js"]).unwrap(),
+            "fetch(\"/items\");\n"
+        );
+        assert_eq!(extract_tagged_code(reply, &["css"]).unwrap(), "body {}\n");
+        assert_eq!(extract_tagged_code(reply, &["rust"]), None);
+    }
+
+    #[test]
+    fn tests_taking_unterminated_block() {
+        let extracted = extract_rust_code("```\nfn main() {\n    println!(\"cut off\");");
+
+        assert_eq!(extracted.code, "fn main() {\n    println!(\"cut off\");\n");
+        assert!(extracted.warning.is_some());
+    }
+}
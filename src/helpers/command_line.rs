@@ -1,10 +1,23 @@
-use std::io::{stdin, stdout};
+use std::{
+    fs,
+    io::{stdin, stdout, Write},
+};
 
 use crossterm::{
     style::{Color, ResetColor, SetForegroundColor},
     ExecutableCommand,
 };
 
+use crate::{
+    apis::{llm_config::LlmConfig, prompts::PromptRegistry},
+    eval::{
+        corpus::{EvalConfig, ResponseSource, DEFAULT_EVAL_PATH},
+        harness::run_eval,
+    },
+};
+
+use super::workspace::Workspace;
+
 #[derive(PartialEq, Debug)]
 pub enum PrintCommand {
     AICall,
@@ -13,15 +26,19 @@ pub enum PrintCommand {
 }
 
 impl PrintCommand {
+    fn statement_color(&self) -> Color {
+        match self {
+            Self::AICall => Color::Cyan,
+            Self::UnitTest => Color::Magenta,
+            Self::Issue => Color::Red,
+        }
+    }
+
     pub fn print_agent_message(&self, agent_pos: &str, agent_statement: &str) {
         let mut stdout: std::io::Stdout = stdout();
 
         // Decide on the print color
-        let statement_color: Color = match self {
-            Self::AICall => Color::Cyan,
-            Self::UnitTest => Color::Magenta,
-            Self::Issue => Color::Red,
-        };
+        let statement_color: Color = self.statement_color();
 
         // Print agent statement in a specific color
         stdout.execute(SetForegroundColor(Color::Green)).unwrap();
@@ -34,6 +51,20 @@ impl PrintCommand {
         // Reset color
         stdout.execute(ResetColor).unwrap();
     }
+
+    // Print part of a streamed response without a line break
+    pub fn print_stream_token(&self, token: &str) {
+        let mut stdout: std::io::Stdout = stdout();
+
+        stdout
+            .execute(SetForegroundColor(self.statement_color()))
+            .unwrap();
+        print!("{}", token);
+        stdout.execute(ResetColor).unwrap();
+
+        // Show the token now rather than when the line ends
+        stdout.flush().unwrap();
+    }
 }
 
 // Get user request
@@ -43,7 +74,7 @@ pub fn get_user_response(question: &str) -> String {
     // Print ther question in specific color
     stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
 
-    println!("");
+    println!();
     println!("{}", question);
 
     // Reset color
@@ -57,7 +88,7 @@ pub fn get_user_response(question: &str) -> String {
         .expect("Failed to read response");
 
     // Trim whitespace and return
-    return user_response.trim().to_string();
+    user_response.trim().to_string()
 }
 
 // Get user response that code is safe to execute
@@ -66,7 +97,7 @@ pub fn confirm_safe_code() -> bool {
     loop {
         // Print the question in specified color
         stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
-        println!("");
+        println!();
         print!("WARNING: You are about to run code written entirely by AI ");
         println!("Review your code and confirm you wish to continue.");
 
@@ -103,6 +134,73 @@ pub fn confirm_safe_code() -> bool {
     }
 }
 
+// `prompts list` shows where each ai_function's prompt comes from,
+// `prompts show <ai_function>` prints the prompt that will be sent
+pub fn run_prompts_command(prompts: &PromptRegistry, args: &[String]) -> Result<(), String> {
+    match args {
+        [] => run_prompts_command(prompts, &["list".to_string()]),
+        [command] if command == "list" => {
+            for (ai_function, source) in prompts.list() {
+                println!("{:<32} {}", ai_function, source);
+            }
+            Ok(())
+        }
+        [command, ai_function] if command == "show" => {
+            let (source, text) = prompts
+                .show(ai_function)
+                .ok_or_else(|| format!("No ai_function named {}", ai_function))?;
+            println!("# {} ({})", ai_function, source);
+            println!("{}", text);
+            Ok(())
+        }
+        _ => Err("Usage: prompts [list | show <ai_function>]".to_string()),
+    }
+}
+
+// `eval [corpus.toml] [--replay | --record | --live]` runs the corpus and
+// writes the comparison table to the corpus' report path
+pub async fn run_eval_command(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: eval [corpus.toml] [--replay | --record | --live]";
+
+    let mut path = DEFAULT_EVAL_PATH;
+    let mut responses = None;
+    for arg in args {
+        match arg.as_str() {
+            "--replay" => responses = Some(ResponseSource::Replay),
+            "--record" => responses = Some(ResponseSource::Record),
+            "--live" => responses = Some(ResponseSource::Live),
+            flag if flag.starts_with("--") => return Err(usage.to_string()),
+            corpus => path = corpus,
+        }
+    }
+
+    let mut eval = EvalConfig::load(path).map_err(|e| e.to_string())?;
+    if let Some(responses) = responses {
+        eval.responses = responses;
+    }
+
+    // Replays never reach the provider, so they need no API key
+    let config = match eval.responses {
+        ResponseSource::Replay => LlmConfig::read(),
+        ResponseSource::Record | ResponseSource::Live => LlmConfig::load(),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let report = run_eval(&eval, &config, &Workspace::from_env())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(dir) = eval.report.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&eval.report, report.to_string())
+        .map_err(|e| format!("Failed to write {}: {}", eval.report.display(), e))?;
+
+    println!("{}", report);
+    println!("Report written to {}", eval.report.display());
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,4 +210,12 @@ mod tests {
         PrintCommand::AICall
             .print_agent_message("Managing Agent", "Testing testing, processing something");
     }
+
+    #[test]
+    fn tests_prints_stream_tokens() {
+        for token in ["fn ", "main", "() {}"] {
+            PrintCommand::AICall.print_stream_token(token);
+        }
+        println!();
+    }
 }
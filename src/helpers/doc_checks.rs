@@ -0,0 +1,479 @@
+// Reads what generated docs can be checked against out of the backend code:
+// the routes it registers and the environment variables it reads
+
+use regex::Regex;
+
+use super::fetch_calls::route_matches;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    // Lowercase, as in RouteObject
+    pub method: String,
+    pub path: String,
+}
+
+// Registration calls followed when reading routes, in one pass so they come out in order
+const REGISTRATION: &str = concat!(
+    r#"web::(?P<frame>scope|resource)\(\s*(?:"(?P<frame_path>[^"]*)")?"#,
+    r#"|\.route\(\s*(?:"(?P<route_path>[^"]*)"\s*,\s*)?(?:web::(?P<route_method>get|post|put|patch|delete|head)\(\))?"#,
+    r#"|\.service\(\s*(?P<service>\w+)\s*\)"#,
+    r#"|\.(?P<shortcut>get|post|put|patch|delete|head|to)\("#,
+    r#"|(?P<unreadable>\.configure\(|#\[route\()"#,
+);
+
+// A web::scope or web::resource and the paren depth its chained calls are made at
+struct Frame {
+    depth: usize,
+    path: String,
+    is_resource: bool,
+}
+
+// Routes registered with actix-web: .route("/item/{id}", web::get()...), #[get("/item/{id}")]
+// handlers, and web::resource("/item").route(web::get()...), each under the web::scope
+// prefixes around it. None when routes are registered in a form this cannot follow,
+// e.g. .configure(...) or a path that is not a string literal.
+pub fn find_registered_routes(code: &str) -> Option<Vec<Endpoint>> {
+    let registration = Regex::new(REGISTRATION).expect("Valid regex");
+    let route_macro = Regex::new(
+        r#"#\[(get|post|put|patch|delete|head)\(\s*"([^"]*)"[^\]]*\][\s\S]*?\bfn\s+(\w+)"#,
+    )
+    .expect("Valid regex");
+
+    // (handler, method, path) for every #[get(...)] style handler
+    let handlers: Vec<(String, String, String)> = route_macro
+        .captures_iter(code)
+        .map(|captures| {
+            (
+                captures[3].to_string(),
+                captures[1].to_string(),
+                captures[2].to_string(),
+            )
+        })
+        .collect();
+    let mut serviced = vec![false; handlers.len()];
+
+    let blanked = blank_strings_and_comments(code);
+    let mut registered: Vec<(String, String)> = vec![];
+    let mut frames: Vec<Frame> = vec![];
+    let mut depth = 0;
+    let mut scanned = 0;
+
+    for captures in registration.captures_iter(code) {
+        let token = captures.get(0).expect("Whole match");
+        // Inside a string or a comment
+        if !blanked[token.start()..].starts_with(&token.as_str()[..1]) {
+            continue;
+        }
+        track_depth(&blanked[scanned..token.start()], &mut depth, &mut frames);
+        scanned = token.start();
+
+        let prefix = frames.last().map_or("", |frame| frame.path.as_str());
+        let resource = frames
+            .last()
+            .filter(|frame| frame.is_resource && frame.depth == depth);
+
+        if let Some(kind) = captures.name("frame") {
+            let path = captures.name("frame_path")?.as_str();
+            frames.push(Frame {
+                depth,
+                path: format!("{}{}", prefix, path),
+                is_resource: kind.as_str() == "resource",
+            });
+        } else if captures.name("unreadable").is_some() {
+            return None;
+        } else if let Some(service) = captures.name("service") {
+            for (index, (handler, method, path)) in handlers.iter().enumerate() {
+                if handler == service.as_str() {
+                    registered.push((method.clone(), format!("{}{}", prefix, path)));
+                    serviced[index] = true;
+                }
+            }
+        } else if let Some(shortcut) = captures.name("shortcut") {
+            // Only resource shortcuts, not every .get( or .to( in the file
+            if let Some(resource) = resource {
+                if shortcut.as_str() == "to" {
+                    return None;
+                }
+                registered.push((shortcut.as_str().to_string(), resource.path.clone()));
+            }
+        } else {
+            let method = captures.name("route_method")?.as_str().to_string();
+            let path = match captures.name("route_path") {
+                Some(path) => format!("{}{}", prefix, path.as_str()),
+                None => resource?.path.clone(),
+            };
+            registered.push((method, path));
+        }
+    }
+
+    // Handlers not passed to .service by name are taken as registered at the root
+    for (index, (_, method, path)) in handlers.into_iter().enumerate() {
+        if !serviced[index] {
+            registered.push((method, path));
+        }
+    }
+
+    let mut routes: Vec<Endpoint> = vec![];
+    for (method, path) in registered {
+        let route = Endpoint { method, path };
+        if !routes.contains(&route) {
+            routes.push(route);
+        }
+    }
+    Some(routes)
+}
+
+// Updates the paren depth over code, closing frames whose chain of calls has ended
+fn track_depth(code: &str, depth: &mut usize, frames: &mut Vec<Frame>) {
+    for character in code.chars() {
+        match character {
+            '(' => *depth += 1,
+            ')' => *depth = depth.saturating_sub(1),
+            ',' | ';' => {
+                while frames.last().is_some_and(|frame| frame.depth >= *depth) {
+                    frames.pop();
+                }
+                continue;
+            }
+            _ => continue,
+        }
+        while frames.last().is_some_and(|frame| frame.depth > *depth) {
+            frames.pop();
+        }
+    }
+}
+
+// Same length as code, with string contents and comments replaced by spaces,
+// so parens in them don't count towards the depth
+fn blank_strings_and_comments(code: &str) -> String {
+    let characters: Vec<char> = code.chars().collect();
+    let mut blanked = String::with_capacity(code.len());
+    let blank = |blanked: &mut String, character: char| {
+        blanked.extend(std::iter::repeat_n(' ', character.len_utf8()))
+    };
+
+    let mut index = 0;
+    while index < characters.len() {
+        let character = characters[index];
+        let next = characters.get(index + 1).copied();
+
+        if character == '/' && next == Some('/') {
+            while index < characters.len() && characters[index] != '\n' {
+                blank(&mut blanked, characters[index]);
+                index += 1;
+            }
+            continue;
+        }
+        if character == '/' && next == Some('*') {
+            while index < characters.len()
+                && !(characters[index] == '*' && characters.get(index + 1) == Some(&'/'))
+            {
+                blank(&mut blanked, characters[index]);
+                index += 1;
+            }
+            let end = characters.len().min(index + 2);
+            for character in &characters[index..end] {
+                blank(&mut blanked, *character);
+            }
+            index = end;
+            continue;
+        }
+        if character == '"' {
+            blanked.push('"');
+            index += 1;
+            while index < characters.len() && characters[index] != '"' {
+                if characters[index] == '\\' {
+                    blank(&mut blanked, characters[index]);
+                    index += 1;
+                }
+                if let Some(character) = characters.get(index) {
+                    blank(&mut blanked, *character);
+                }
+                index += 1;
+            }
+            if index < characters.len() {
+                blanked.push('"');
+                index += 1;
+            }
+            continue;
+        }
+        // Char literals like '(' or '"', but not lifetimes
+        if character == '\'' {
+            let length = match (next, characters.get(index + 2)) {
+                (Some('\\'), _) => characters[index + 2..]
+                    .iter()
+                    .position(|character| *character == '\'')
+                    .map(|end| end + 3),
+                (Some(_), Some('\'')) => Some(3),
+                _ => None,
+            };
+            if let Some(length) = length {
+                let end = characters.len().min(index + length);
+                for character in &characters[index..end] {
+                    blank(&mut blanked, *character);
+                }
+                index = end;
+                continue;
+            }
+        }
+
+        blanked.push(character);
+        index += 1;
+    }
+    blanked
+}
+
+// Names passed to env::var, in the order first read
+pub fn find_env_vars(code: &str) -> Vec<String> {
+    let env_read =
+        Regex::new(r#"\b(?:env::var(?:_os)?|env!|option_env!)\(\s*"(\w+)""#).expect("Valid regex");
+
+    let mut names: Vec<String> = vec![];
+    for captures in env_read.captures_iter(code) {
+        if !names.iter().any(|name| name == &captures[1]) {
+            names.push(captures[1].to_string());
+        }
+    }
+    names
+}
+
+// Every curl command in markdown, with the method curl would send and the path it requests
+pub fn find_curl_calls(markdown: &str) -> Vec<Endpoint> {
+    let method_flag = Regex::new(r#"(?:-X|--request)\s*['"]?(\w+)"#).expect("Valid regex");
+    let data_flag = Regex::new(r"\s(?:-d|-F|--data\S*|--form|--json)\s").expect("Valid regex");
+    let url = Regex::new(r#"https?://[^\s'"`]+"#).expect("Valid regex");
+
+    // Commands split over several lines with a trailing backslash
+    let joined = markdown.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    joined
+        .lines()
+        .filter_map(|line| {
+            let command = &line[line.find("curl ")?..];
+            let url = url.find(command)?.as_str();
+
+            let method = match method_flag.captures(command) {
+                Some(captures) => captures[1].to_lowercase(),
+                None if data_flag.is_match(command) => "post".to_string(),
+                None => "get".to_string(),
+            };
+
+            let host_and_path = url.split_once("://").map_or(url, |(_, rest)| rest);
+            let path = host_and_path
+                .find('/')
+                .map_or("/", |i| &host_and_path[i..])
+                .split(['?', '#'])
+                .next()
+                .unwrap_or_default();
+
+            Some(Endpoint {
+                method,
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+// curl examples that call a route the code does not register
+pub fn check_curl_calls(markdown: &str, routes: &[Endpoint]) -> Vec<String> {
+    let mut errors = vec![];
+
+    for call in find_curl_calls(markdown) {
+        let is_registered = routes
+            .iter()
+            .any(|route| route.method == call.method && route_matches(&route.path, &call.path));
+
+        let error = format!(
+            "curl example {} {} calls a route the code does not register",
+            call.method.to_uppercase(),
+            call.path
+        );
+        if !is_registered && !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+
+    errors
+}
+
+// Environment variables the markdown never mentions by their full name
+pub fn check_env_vars(markdown: &str, env_vars: &[String]) -> Vec<String> {
+    env_vars
+        .iter()
+        .filter(|env_var| {
+            let mention =
+                Regex::new(&format!(r"\b{}\b", regex::escape(env_var))).expect("Valid regex");
+            !mention.is_match(markdown)
+        })
+        .map(|env_var| format!("Environment variable {} is not documented", env_var))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_reading_backend_code() {
+        let code = r#"
+        #[get("/health")]
+        async fn health() -> impl Responder { HttpResponse::Ok() }
+
+        #[actix_web::main]
+        async fn main() -> std::io::Result<()> {
+            let port = env::var("PORT").unwrap_or("8080".to_string());
+            let db = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+            let port_again = env::var("PORT");
+            HttpServer::new(move || {
+                App::new()
+                    .route("/item", web::post().to(create_item))
+                    .route("/item/{id}", web::get().to(read_item))
+                    .route(
+                        "/item/{id}",
+                        web::delete().to(delete_item),
+                    )
+            })
+        }
+        "#;
+
+        let routes: Vec<String> = find_registered_routes(code)
+            .unwrap()
+            .iter()
+            .map(|route| format!("{} {}", route.method, route.path))
+            .collect();
+
+        assert_eq!(
+            routes,
+            [
+                "post /item",
+                "get /item/{id}",
+                "delete /item/{id}",
+                "get /health"
+            ]
+        );
+        assert_eq!(find_env_vars(code), ["PORT", "DATABASE_URL"]);
+    }
+
+    #[test]
+    fn tests_reading_scoped_routes() {
+        let code = r#"
+        #[get("/health")]
+        async fn health() -> impl Responder { HttpResponse::Ok().body("ok :)") }
+
+        #[post("/login")]
+        pub async fn login(form: web::Json<Login>) -> impl Responder { HttpResponse::Ok() }
+
+        App::new()
+            .service(health)
+            // .route("/commented", web::get().to(nothing))
+            .service(
+                web::scope("/api")
+                    .service(login)
+                    .service(web::resource("/items").route(web::get().to(list)).post(create))
+                    .service(
+                        web::scope("/v2")
+                            .route("/items/{id}", web::get().to(|path: web::Path<u32>| async move {
+                                format!("item {}", path.into_inner())
+                            }))
+                    )
+                    .route("/items/{id}", web::delete().to(delete_item)),
+            )
+            .route("/", web::get().to(index))
+        "#;
+
+        let routes: Vec<String> = find_registered_routes(code)
+            .unwrap()
+            .iter()
+            .map(|route| format!("{} {}", route.method, route.path))
+            .collect();
+
+        assert_eq!(
+            routes,
+            [
+                "get /health",
+                "post /api/login",
+                "get /api/items",
+                "post /api/items",
+                "get /api/v2/items/{id}",
+                "delete /api/items/{id}",
+                "get /",
+            ]
+        );
+
+        // Registered somewhere this cannot see
+        assert_eq!(
+            find_registered_routes("App::new().configure(routes::config)"),
+            None
+        );
+        assert_eq!(
+            find_registered_routes(r#"web::resource("/items").to(handle_any)"#),
+            None
+        );
+        assert_eq!(
+            find_registered_routes(r#".route(ITEMS_PATH, web::get().to(list))"#),
+            None
+        );
+    }
+
+    #[test]
+    fn tests_checking_curl_examples() {
+        let markdown = r#"
+```sh
+curl http://localhost:8080/item/1?verbose=true
+curl -X DELETE "http://localhost:8080/item/1"
+curl http://localhost:8080/item \
+  -H "Content-Type: application/json" \
+  -d '{"name": "milk"}'
+curl --request PUT http://localhost:8080/item/1
+```
+"#;
+
+        assert_eq!(
+            find_curl_calls(markdown),
+            [
+                Endpoint {
+                    method: "get".to_string(),
+                    path: "/item/1".to_string(),
+                },
+                Endpoint {
+                    method: "delete".to_string(),
+                    path: "/item/1".to_string(),
+                },
+                Endpoint {
+                    method: "post".to_string(),
+                    path: "/item".to_string(),
+                },
+                Endpoint {
+                    method: "put".to_string(),
+                    path: "/item/1".to_string(),
+                },
+            ]
+        );
+
+        let routes = find_registered_routes(
+            r#".route("/item", web::post().to(create_item))
+            .route("/item/{id}", web::get().to(read_item))
+            .route("/item/{id}", web::delete().to(delete_item))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            check_curl_calls(markdown, &routes),
+            ["curl example PUT /item/1 calls a route the code does not register"]
+        );
+    }
+
+    #[test]
+    fn tests_checking_env_vars() {
+        let env_vars = ["PORT".to_string(), "DATABASE_URL".to_string()];
+
+        // PORTS and DATABASE_URL_FILE do not document either variable
+        assert_eq!(
+            check_env_vars("Both PORTS are set from DATABASE_URL_FILE.", &env_vars),
+            [
+                "Environment variable PORT is not documented",
+                "Environment variable DATABASE_URL is not documented"
+            ]
+        );
+        assert!(check_env_vars("Set `PORT` and DATABASE_URL.", &env_vars).is_empty());
+    }
+}
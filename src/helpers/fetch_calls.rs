@@ -0,0 +1,260 @@
+// Finds the backend endpoints generated frontend code calls with fetch,
+// so they can be checked against the API schema
+
+use regex::Regex;
+
+// Path segments built at runtime, `${id}` or "/items/" + id, become this
+const DYNAMIC_SEGMENT: &str = "{}";
+
+// Stands in for dynamic parts until the base url is stripped
+const DYNAMIC_MARKER: char = '\u{0}';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchCall {
+    // Lowercase, as in RouteObject
+    pub method: String,
+    // None when the url has no literal text to go on, e.g. fetch(url)
+    pub path: Option<String>,
+}
+
+// Every fetch( call in order, with the path it requests and its method
+pub fn find_fetch_calls(source: &str) -> Vec<FetchCall> {
+    let method_option = Regex::new(r#"method\s*:\s*["'`](\w+)["'`]"#).expect("Valid regex");
+    let mut calls = vec![];
+
+    for (start, _) in source.match_indices("fetch(") {
+        // Skip other functions ending in fetch, like prefetch(
+        let is_prefixed = source[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$');
+        if is_prefixed {
+            continue;
+        }
+
+        let arguments = call_arguments(&source[start + "fetch(".len()..]);
+        let Some(url) = arguments.first() else {
+            continue;
+        };
+
+        let method = arguments
+            .get(1)
+            .and_then(|options| method_option.captures(options))
+            .map_or("get".to_string(), |captures| captures[1].to_lowercase());
+
+        calls.push(FetchCall {
+            method,
+            path: url_path(url),
+        });
+    }
+
+    calls
+}
+
+// Whether a called path is served by a route, {param} segments match any value
+pub fn route_matches(route: &str, path: &str) -> bool {
+    let route_segments: Vec<&str> = route.trim_matches('/').split('/').collect();
+    let path_segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    route_segments.len() == path_segments.len()
+        && route_segments
+            .iter()
+            .zip(&path_segments)
+            .all(|(route_segment, path_segment)| {
+                let is_param = route_segment.starts_with('{') && route_segment.ends_with('}');
+                match is_param {
+                    true => !path_segment.is_empty(),
+                    false => route_segment == path_segment,
+                }
+            })
+}
+
+// Top level arguments of a call, read up to its closing parenthesis
+fn call_arguments(text: &str) -> Vec<String> {
+    let mut arguments = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if let Some(open) = quote {
+            current.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                _ if c == open => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' | '`' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() {
+        arguments.push(current.trim().to_string());
+    }
+    arguments
+}
+
+// The path a url expression requests, without the base url, query or fragment
+fn url_path(expression: &str) -> Option<String> {
+    let mut url = String::new();
+    let mut has_literal = false;
+
+    for part in split_concatenation(expression) {
+        match string_literal(&part) {
+            Some(text) => {
+                has_literal = true;
+                url.push_str(&text);
+            }
+            None => url.push(DYNAMIC_MARKER),
+        }
+    }
+
+    if !has_literal {
+        return None;
+    }
+
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let url = match url.split_once("://") {
+        Some((_, host_and_path)) => host_and_path.find('/').map(|i| &host_and_path[i..])?,
+        // Anything before the first slash is a base url variable
+        None => &url[url.find('/')?..],
+    };
+
+    let segments: Vec<&str> = url
+        .split('/')
+        .map(|segment| match segment.contains(DYNAMIC_MARKER) {
+            true => DYNAMIC_SEGMENT,
+            false => segment,
+        })
+        .collect();
+
+    Some(segments.join("/"))
+}
+
+// Operands of `a + "b" + c`, splitting only outside of strings and brackets
+fn split_concatenation(expression: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in expression.chars() {
+        if let Some(open) = quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                _ if c == open => quote = None,
+                _ => {}
+            }
+        } else {
+            match c {
+                '"' | '\'' | '`' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                '+' if depth == 0 => {
+                    parts.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        current.push(c);
+    }
+
+    parts.push(current.trim().to_string());
+    parts
+}
+
+// The text of a quoted string, template placeholders marked as dynamic
+fn string_literal(part: &str) -> Option<String> {
+    let quote = part
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let inner = part.strip_prefix(quote)?.strip_suffix(quote)?;
+
+    if quote != '`' {
+        return Some(inner.to_string());
+    }
+
+    let mut text = String::new();
+    let mut rest = inner;
+    while let Some(start) = rest.find("${") {
+        text.push_str(&rest[..start]);
+        text.push(DYNAMIC_MARKER);
+        rest = rest[start..]
+            .find('}')
+            .map_or("", |end| &rest[start + end + 1..]);
+    }
+    text.push_str(rest);
+
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_finding_fetch_calls() {
+        let source = r#"
+        const API_BASE = "http://localhost:8080";
+        async function load() {
+            const items = await fetch(`${API_BASE}/items?done=false`).then((r) => r.json());
+            await fetch(API_BASE + "/items/" + item.id, { method: "DELETE" });
+            await fetch("http://localhost:8080/items", {
+                method: 'post',
+                body: JSON.stringify({ name: "a, b" }),
+            });
+            await fetch(`/items/${id}/done`, { method: "PUT" });
+            prefetch("/ignored");
+            await fetch(url);
+        }
+        "#;
+
+        let calls: Vec<(String, Option<String>)> = find_fetch_calls(source)
+            .into_iter()
+            .map(|call| (call.method, call.path))
+            .collect();
+
+        assert_eq!(
+            calls,
+            [
+                ("get".to_string(), Some("/items".to_string())),
+                ("delete".to_string(), Some("/items/{}".to_string())),
+                ("post".to_string(), Some("/items".to_string())),
+                ("put".to_string(), Some("/items/{}/done".to_string())),
+                ("get".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn tests_matching_paths_to_routes() {
+        assert!(route_matches("/items/{id}", "/items/{}"));
+        assert!(route_matches("/items/{id}", "/items/42"));
+        assert!(route_matches("/items/", "/items"));
+        assert!(route_matches("/", "/"));
+        assert!(!route_matches("/items", "/items/{}"));
+        assert!(!route_matches("/items/done", "/items/{}"));
+        assert!(!route_matches("/users/{id}", "/items/{}"));
+    }
+}
@@ -1,29 +1,46 @@
-use std::fs;
+use std::{fs, time::Instant};
 
 use reqwest::Client;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
+use toml_edit::{table, value, Array, DocumentMut, InlineTable};
 
-use crate::{apis::call_request::call_gpt, models::general::llm::Message};
+use crate::{
+    ai_functions::aifunc_managing::{print_repaired_json, summarize_conversation},
+    apis::{
+        context::{ContextStrategy, ContextWindow},
+        llm_client::LlmClient,
+        llm_error::LlmError,
+        response_cache::ResponseCache,
+        retry::with_retry,
+        routing::allows_fallback,
+        structured_output::StructuredRequest,
+        transcript::Exchange,
+    },
+    models::{
+        agent_basic::basic_agent::BasicAgent,
+        agents::agent_traits::Migration,
+        general::llm::{ChatOptions, LlmResponse, Message, Usage},
+    },
+};
 
-use super::command_line::PrintCommand;
+use super::{command_line::PrintCommand, json_repair::candidates, workspace::Workspace};
 
-pub const WEB_SERVER_PROJECT_PATH: &str = r#"D:\rust\web_server"#;
-
-const CODE_TEMPLATE_PATH: &str = r#"D:\rust\web_server\src\code_template.rs"#;
-const EXEC_TEMPLATE_PATH: &str = r#"D:\rust\web_server\src\main.rs"#;
-const API_SCHEMA_PATH: &str = "/d/rust/web_server/src/schemas/api_schema.json";
-
-// Extend ai function to encourage specific output
-pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> Message {
-    let ai_function_str = ai_func(func_input);
+// Extend ai function to encourage specific output.
+// func_input is None when the function text already has the input in it.
+pub fn extend_ai_function(ai_function_str: &str, func_input: Option<&str>) -> Message {
+    let input_line = match func_input {
+        Some(func_input) => format!("Here is the input to the function: {}. ", func_input),
+        None => String::new(),
+    };
 
     // Extend the string to encourage only printing  the output
     let msg: String = format!(
         "FUNCTION: {} 
     INSTRUCTION: You are a function printer. 
     You ONLY print the results of functions. Nothing else. No commentary. 
-    Here is the input to the function: {}. Print out what the function will return.",
-        ai_function_str, func_input
+    {}Print out what the function will return.",
+        ai_function_str, input_line
     );
 
     // Return message
@@ -33,45 +50,378 @@ pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -
     }
 }
 
-// Performs call to GPT
+// The extended prompt for an ai_function, using the prompt template for it when there is one.
+// A template with {{input}} in it carries the input itself, so it is not appended again.
+fn function_prompt(
+    llm: &dyn LlmClient,
+    agent_operation: &str,
+    callback_fn: for<'a> fn(&'a str) -> &'static str,
+    func_input: &str,
+) -> Message {
+    let prompts = &llm.config().prompts;
+    let ai_function_str = prompts.render(agent_operation, callback_fn, func_input);
+
+    match prompts.embeds_input(agent_operation) {
+        true => extend_ai_function(&ai_function_str, None),
+        false => extend_ai_function(&ai_function_str, Some(func_input)),
+    }
+}
+
+// Performs call to LLM, remembering the exchange in the agent's memory.
+// With send_history the agent's earlier exchanges are sent along with the prompt.
 pub async fn ai_task_request(
+    llm: &dyn LlmClient,
     msg_context: String,
-    agent_position: &str,
+    agent: &mut BasicAgent,
     agent_operation: &str,
     callback_fn: for<'a> fn(&'a str) -> &'static str,
-) -> String {
+    send_history: bool,
+) -> Result<String, LlmError> {
+    ai_task_request_with(
+        llm,
+        msg_context,
+        agent,
+        agent_operation,
+        callback_fn,
+        send_history,
+        &ChatOptions::default(),
+    )
+    .await
+}
+
+async fn ai_task_request_with(
+    llm: &dyn LlmClient,
+    msg_context: String,
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    callback_fn: for<'a> fn(&'a str) -> &'static str,
+    send_history: bool,
+    options: &ChatOptions,
+) -> Result<String, LlmError> {
+    // Model and sampling settings routed to this ai_function
+    let options = llm.config().routes.options_for(agent_operation, options);
+
     // Extend AI function
-    let extended_msg: Message = extend_ai_function(callback_fn, &msg_context);
+    let extended_msg: Message = function_prompt(llm, agent_operation, callback_fn, &msg_context);
 
-    PrintCommand::AICall.print_agent_message(agent_position, agent_operation);
+    PrintCommand::AICall.print_agent_message(&agent.position, agent_operation);
 
-    // Get GPT response
-    let gpt_response_result: Result<String, Box<dyn std::error::Error + Send>> =
-        call_gpt(vec![extended_msg.clone()]).await;
+    // History is sent as a plain user/assistant transcript so every provider accepts it
+    let messages = if send_history && !agent.memory.is_empty() {
+        let mut messages = agent.memory.clone();
+        messages.push(Message {
+            role: "user".to_string(),
+            content: extended_msg.content.clone(),
+        });
+        messages
+    } else {
+        vec![extended_msg.clone()]
+    };
 
-    // Handle Success
-    match gpt_response_result {
-        Ok(gpt_response) => gpt_response,
-        Err(_) => call_gpt(vec![extended_msg.clone()])
-            .await
-            .expect("Failed twice to call GPT"),
+    let messages = fit_context(llm, agent, messages, &options).await?;
+    let content =
+        request_content(llm, messages, &options, &agent.position, agent_operation).await?;
+
+    agent.memory.push(Message {
+        role: "user".to_string(),
+        content: extended_msg.content,
+    });
+    agent.memory.push(Message {
+        role: "assistant".to_string(),
+        content: content.clone(),
+    });
+
+    Ok(content)
+}
+
+// Keep the prompt inside the model's context window using the configured strategy
+async fn fit_context(
+    llm: &dyn LlmClient,
+    agent: &mut BasicAgent,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+) -> Result<Vec<Message>, LlmError> {
+    let window = ContextWindow::new(llm.config(), options);
+    let trimmed = window.trim(messages)?;
+
+    if trimmed.dropped.is_empty() {
+        return Ok(trimmed.kept);
     }
+
+    if llm.config().context.strategy == ContextStrategy::DropOldest {
+        PrintCommand::Issue.print_agent_message(
+            &agent.position,
+            &format!(
+                "Context limit reached, left out {} older messages",
+                trimmed.dropped.len()
+            ),
+        );
+        return Ok(trimmed.kept);
+    }
+
+    PrintCommand::AICall.print_agent_message(
+        &agent.position,
+        &format!(
+            "Context limit reached, summarizing {} older messages",
+            trimmed.dropped.len()
+        ),
+    );
+
+    // Leave room for the summarize prompt itself
+    let transcript = trimmed
+        .dropped
+        .iter()
+        .map(|message| format!("[{}] {}", message.role, message.content))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let transcript = window.truncate(&transcript, window.available() / 2);
+
+    let summarize_msg = function_prompt(
+        llm,
+        get_function_string!(summarize_conversation),
+        summarize_conversation,
+        &transcript,
+    );
+    let summary = request_content(
+        llm,
+        vec![summarize_msg],
+        &llm.config().routes.options_for(
+            get_function_string!(summarize_conversation),
+            &ChatOptions::default(),
+        ),
+        &agent.position,
+        get_function_string!(summarize_conversation),
+    )
+    .await?;
+
+    let summary = Message {
+        role: "user".to_string(),
+        content: format!("SUMMARY OF EARLIER CONVERSATION: {}", summary),
+    };
+
+    // The summary replaces what it covers in memory too, so later calls don't redo it
+    agent
+        .memory
+        .retain(|message| !trimmed.dropped.contains(message));
+    agent.memory.insert(0, summary.clone());
+
+    // Pinned system messages stay in front of the summary
+    let pinned = trimmed
+        .kept
+        .iter()
+        .take_while(|message| message.role == "system")
+        .count();
+    let mut messages = trimmed.kept;
+    messages.insert(pinned, summary);
+
+    Ok(window.trim(messages)?.kept)
 }
 
-// Performs call to GPT - Decoded
-pub async fn ai_task_request_decoded<T: DeserializeOwned>(
-    msg_context: String,
+// Serve messages from the cache or the LLM, recording usage for the agent.
+// Tool call arguments stand in for the content when the model called a tool.
+// When the model fails for good, the ai_function's fallback models are tried in turn.
+async fn request_content(
+    llm: &dyn LlmClient,
+    messages: Vec<Message>,
+    options: &ChatOptions,
     agent_position: &str,
     agent_operation: &str,
+) -> Result<String, LlmError> {
+    let config = llm.config();
+    let mut fallbacks = config.routes.fallback_models(agent_operation).iter();
+    let mut options = options.clone();
+
+    loop {
+        match request_model(llm, &messages, &options, agent_position, agent_operation).await {
+            Err(e) if allows_fallback(&e) => {
+                let Some(fallback) = fallbacks.next() else {
+                    return Err(e);
+                };
+
+                PrintCommand::Issue.print_agent_message(
+                    agent_position,
+                    &format!(
+                        "{} failed ({}), falling back to {}",
+                        options.model.as_deref().unwrap_or(&config.model),
+                        e,
+                        fallback
+                    ),
+                );
+                options.model = Some(fallback.clone());
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn request_model(
+    llm: &dyn LlmClient,
+    messages: &[Message],
+    options: &ChatOptions,
+    agent_position: &str,
+    agent_operation: &str,
+) -> Result<String, LlmError> {
+    let config = llm.config();
+    let model = options.model.as_deref().unwrap_or(&config.model);
+    let cache_key = ResponseCache::key(config, messages, options);
+
+    // A cached completion costs nothing, so it is recorded without tokens
+    if let Some(cached) = config.cache.get(&cache_key) {
+        PrintCommand::AICall.print_agent_message(agent_position, "Using cached response");
+        llm.usage()
+            .record(agent_position, agent_operation, model, Usage::default());
+        return Ok(cached.output());
+    }
+
+    // Get LLM response, retrying transient failures. The budget is checked
+    // before every attempt so retries cannot overspend it either.
+    // Every attempt is logged to the transcript, failed ones included.
+    let llm_response = with_retry(&config.retry, agent_position, || async {
+        config.budget.check(llm.usage(), &config.prices)?;
+
+        let started = Instant::now();
+        let result = request_completion(llm, messages.to_vec(), options).await;
+
+        let exchange = Exchange {
+            agent_position,
+            ai_function: agent_operation,
+            model,
+            messages,
+            options,
+            result: &result,
+            latency: started.elapsed(),
+        };
+        if let Err(e) = llm.transcript().log(exchange) {
+            PrintCommand::Issue.print_agent_message(
+                agent_position,
+                &format!("Failed to write transcript: {}", e),
+            );
+        }
+
+        result
+    })
+    .await?;
+
+    // Attribute tokens to the calling agent and ai_function
+    llm.usage()
+        .record(agent_position, agent_operation, model, llm_response.usage);
+
+    // A failed write only loses the cache entry, not the completion
+    if let Err(e) = config.cache.put(&cache_key, model, messages, &llm_response) {
+        PrintCommand::Issue
+            .print_agent_message(agent_position, &format!("Failed to cache response: {}", e));
+    }
+
+    Ok(llm_response.output())
+}
+
+// Send messages, streaming tokens to the terminal when enabled in config.
+// Structured requests are never streamed, partial JSON is of no use to read.
+async fn request_completion(
+    llm: &dyn LlmClient,
+    messages: Vec<Message>,
+    options: &ChatOptions,
+) -> Result<LlmResponse, LlmError> {
+    if !llm.config().stream || options.is_structured() {
+        return llm.chat(messages, options).await;
+    }
+
+    let mut print_token = |token: &str| PrintCommand::AICall.print_stream_token(token);
+    let result = llm.chat_stream(messages, options, &mut print_token).await;
+
+    // Finish the streamed line
+    println!();
+
+    result
+}
+
+// Performs call to LLM - Decoded
+// The configured structured_output mode constrains the reply to T's JSON schema.
+// Output that still fails to decode is sent back with the parse error to be fixed.
+pub async fn ai_task_request_decoded<T: DeserializeOwned + JsonSchema>(
+    llm: &dyn LlmClient,
+    msg_context: String,
+    agent: &mut BasicAgent,
+    agent_operation: &str,
     callback_fn: for<'a> fn(&'a str) -> &'static str,
-) -> T {
-    let llm_response =
-        ai_task_request(msg_context, agent_position, agent_operation, callback_fn).await;
+    send_history: bool,
+) -> Result<T, LlmError> {
+    let mode = llm.config().structured_output;
+    let retries = llm.config().decode_retries;
+    let request = StructuredRequest::new::<T>(mode, agent_operation);
+    let repair_request =
+        StructuredRequest::new::<T>(mode, get_function_string!(print_repaired_json));
+
+    let mut output = ai_task_request_with(
+        llm,
+        msg_context,
+        agent,
+        agent_operation,
+        callback_fn,
+        send_history,
+        &request.options,
+    )
+    .await?;
+
+    let mut attempt = 0;
+    loop {
+        let source = match decode_output(&request, &output) {
+            Ok(decoded) => return Ok(decoded),
+            Err(source) => source,
+        };
+
+        if attempt == retries {
+            return Err(LlmError::Decode {
+                source,
+                body: output,
+            });
+        }
+        attempt += 1;
+
+        PrintCommand::Issue.print_agent_message(
+            &agent.position,
+            &format!(
+                "Could not decode {} output ({}), asking for a fix {} of {}",
+                agent_operation, source, attempt, retries
+            ),
+        );
 
-    let decoded_response: T = serde_json::from_str(&llm_response.as_str())
-        .expect("Failed to decode ai response from serde_json");
+        let repair_context = format!(
+            "EXPECTED_SCHEMA: {} ERROR: {} INVALID_OUTPUT: {}",
+            repair_request.schema(),
+            source,
+            output
+        );
 
-    decoded_response
+        output = ai_task_request_with(
+            llm,
+            repair_context,
+            agent,
+            get_function_string!(print_repaired_json),
+            print_repaired_json,
+            false,
+            &repair_request.options,
+        )
+        .await?;
+    }
+}
+
+// Try the output as is, then with fences and prose removed, then repaired.
+// The error reported is the one for the most repaired form.
+fn decode_output<T: DeserializeOwned>(
+    request: &StructuredRequest,
+    output: &str,
+) -> Result<T, serde_json::Error> {
+    let mut last_error = None;
+
+    for candidate in candidates(output) {
+        match request.decode(&candidate) {
+            Ok(decoded) => return Ok(decoded),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.expect("There is always at least one candidate"))
 }
 
 // Check whether request url is valid
@@ -82,36 +432,105 @@ pub async fn check_status_code(client: &Client, url: &str) -> Result<u16, reqwes
 }
 
 // Get Code Template
-pub fn read_code_template_contents() -> String {
-    let path = String::from(CODE_TEMPLATE_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+pub fn read_code_template_contents(workspace: &Workspace) -> String {
+    fs::read_to_string(workspace.code_template_path()).expect("Failed to read code template")
 }
 
-pub fn read_exec_main_contents() -> String {
-    let path = String::from(EXEC_TEMPLATE_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+pub fn read_exec_main_contents(workspace: &Workspace) -> String {
+    fs::read_to_string(workspace.main_path()).expect("Failed to read code template")
 }
 
 // Save nNew Backend Code
-pub fn save_backend_code(content: &String) {
-    let path = String::from(EXEC_TEMPLATE_PATH);
-    fs::write(path, content).expect("Failed to write main.rs file");
+pub fn save_backend_code(workspace: &Workspace, content: &String) {
+    fs::write(workspace.main_path(), content).expect("Failed to write main.rs file");
 }
 
 // Save JSON API Endpoint Schema
-pub fn save_api_endpoints(api_endpoints: &String) {
-    let path = String::from(API_SCHEMA_PATH);
-    fs::write(path, api_endpoints).expect("Failed to write API endpoints to file");
+pub fn save_api_endpoints(workspace: &Workspace, api_endpoints: &String) {
+    fs::write(workspace.api_schema_path(), api_endpoints)
+        .expect("Failed to write API endpoints to file");
+}
+
+// Save Integration Tests
+pub fn save_integration_tests(workspace: &Workspace, content: &str) {
+    let path = workspace.integration_tests_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("Failed to create tests directory");
+    }
+    fs::write(path, content).expect("Failed to write integration tests");
+}
+
+// Save SQL Migration
+pub fn save_migration(workspace: &Workspace, migration: &Migration) {
+    let dir = workspace.migrations_dir();
+    fs::create_dir_all(&dir).expect("Failed to create migrations directory");
+    fs::write(dir.join(&migration.name), &migration.sql).expect("Failed to write migration file");
+}
+
+// Add a Dependency to the Project, keeping the rest of Cargo.toml as it is
+pub fn add_project_dependency(workspace: &Workspace, name: &str, version: &str, features: &[&str]) {
+    let path = workspace.cargo_toml_path();
+    let mut manifest: DocumentMut = fs::read_to_string(&path)
+        .expect("Failed to read Cargo.toml")
+        .parse()
+        .expect("Failed to parse Cargo.toml");
+
+    let mut dependency = InlineTable::new();
+    dependency.insert("version", version.into());
+    if !features.is_empty() {
+        dependency.insert(
+            "features",
+            Array::from_iter(features.iter().copied()).into(),
+        );
+    }
+    manifest["dependencies"].or_insert(table())[name] = value(dependency);
+
+    fs::write(path, manifest.to_string()).expect("Failed to write Cargo.toml");
+}
+
+// Save a Frontend File
+pub fn save_frontend_file(workspace: &Workspace, file_name: &str, content: &str) {
+    let dir = workspace.frontend_dir();
+    fs::create_dir_all(&dir).expect("Failed to create frontend directory");
+    fs::write(dir.join(file_name), content).expect("Failed to write frontend file");
+}
+
+// Save README
+pub fn save_readme(workspace: &Workspace, content: &str) {
+    fs::write(workspace.readme_path(), content).expect("Failed to write README");
+}
+
+// Save API Reference
+pub fn save_api_reference(workspace: &Workspace, content: &str) {
+    let path = workspace.api_reference_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("Failed to create docs directory");
+    }
+    fs::write(path, content).expect("Failed to write API reference");
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
+    use crate::{
+        ai_functions::{
+            aifunc_architect::{print_project_scope, print_site_urls},
+            aifunc_backend::print_fixed_code,
+            aifunc_managing::convert_user_input_to_goal,
+        },
+        apis::{
+            cassette::cassette_client,
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+            routing::Route,
+            structured_output::StructuredOutput,
+        },
+        models::{agent_basic::basic_traits::BasicTrait, agents::agent_traits::ProjectScope},
+    };
 
     #[test]
     fn tests_extending_ai_function() {
-        let extended_msg = extend_ai_function(convert_user_input_to_goal, "dummy");
+        let extended_msg = extend_ai_function(convert_user_input_to_goal("dummy"), Some("dummy"));
 
         assert_eq!(extended_msg.role, "system".to_string())
     }
@@ -120,14 +539,279 @@ mod tests {
     async fn test_ai_task_request() {
         let ai_func_param = "build be a web server for making stock price api requests".to_string();
 
+        let llm = cassette_client("ai_task_request");
+        let mut agent = BasicAgent::new("Test".to_string(), "Managing Agent".to_string());
+
         let res = ai_task_request(
+            llm.as_ref(),
             ai_func_param,
-            "Managing Agent",
+            &mut agent,
             "Defining user requirements",
             convert_user_input_to_goal,
+            false,
         )
-        .await;
+        .await
+        .expect("Failed to call LLM");
 
         assert!(res.len() > 20);
+        assert_eq!(agent.get_memory().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn tests_sending_agent_history() {
+        let script = MockScript::new()
+            .reply("print_fixed_code", "fn main() { broken }")
+            .reply("print_fixed_code", "fn main() {}");
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = BasicAgent::new("Test".to_string(), "Backend Developer".to_string());
+
+        for errors in ["error: expected `;`", "error: cannot find value `broken`"] {
+            ai_task_request(
+                llm.as_ref(),
+                format!("BROKEN_CODE: fn main() {{}} ERROR_BUGS: {}", errors),
+                &mut agent,
+                "print_fixed_code",
+                print_fixed_code,
+                true,
+            )
+            .await
+            .unwrap();
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests[0]["messages"].as_array().unwrap().len(), 1);
+
+        // The second attempt sees the first prompt and reply before its own prompt
+        let second = requests[1]["messages"].as_array().unwrap();
+        let roles: Vec<&str> = second.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user"]);
+        assert_eq!(second[1]["content"], "fn main() { broken }");
+
+        assert_eq!(agent.get_memory().len(), 4);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tests_summarizing_history_over_context_limit() {
+        let script = MockScript::new()
+            .reply(
+                "summarize_conversation",
+                "Tried twice, build fails on a missing semicolon",
+            )
+            .reply("print_fixed_code", "fn main() {}");
+
+        let server = MockLlmServer::start(script).await;
+        let mut config = server.config();
+        config.context.limit = Some(config.max_tokens as usize + 600);
+        config.context.strategy = ContextStrategy::Summarize;
+        let llm = create_llm_client(config).unwrap();
+
+        let mut agent = BasicAgent::new("Test".to_string(), "Backend Developer".to_string());
+        for role in ["user", "assistant", "user", "assistant"] {
+            agent.memory.push(Message {
+                role: role.to_string(),
+                content: "fn main() { let x = 1 }\n".repeat(40),
+            });
+        }
+
+        ai_task_request(
+            llm.as_ref(),
+            "BROKEN_CODE: fn main() { let x = 1 } ERROR_BUGS: expected `;`".to_string(),
+            &mut agent,
+            "print_fixed_code",
+            print_fixed_code,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            server.calls(),
+            vec!["summarize_conversation", "print_fixed_code"]
+        );
+
+        let sent = server.requests()[1]["messages"].as_array().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0]["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("SUMMARY OF EARLIER CONVERSATION: Tried twice"));
+
+        // Memory keeps the summary in place of the exchanges it covers
+        let roles: Vec<&str> = agent.memory.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "user", "assistant"]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tests_decoding_schema_constrained_tool_call() {
+        let script = MockScript::new().reply(
+            "print_site_urls",
+            r#"{"value": ["https://api.binance.com/api/v3/ticker/price"]}"#,
+        );
+
+        let server = MockLlmServer::start(script).await;
+        let mut config = server.config();
+        config.structured_output = StructuredOutput::Tool;
+        let llm = create_llm_client(config).unwrap();
+        let mut agent = BasicAgent::new("Test".to_string(), "Solutions Architect".to_string());
+
+        let urls = ai_task_request_decoded::<Vec<String>>(
+            llm.as_ref(),
+            "a forex and crypto price tracker".to_string(),
+            &mut agent,
+            "print_site_urls",
+            print_site_urls,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(urls, vec!["https://api.binance.com/api/v3/ticker/price"]);
+
+        let request = &server.requests()[0];
+        assert_eq!(
+            request["tool_choice"]["function"]["name"],
+            "print_site_urls"
+        );
+        assert_eq!(
+            request["tools"][0]["function"]["parameters"]["properties"]["value"]["type"],
+            "array"
+        );
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tests_reprompting_with_decode_error() {
+        let script = MockScript::new()
+            .reply(
+                "print_project_scope",
+                "Sure, here is the scope: {\"is_crud_required\": true, \"is_user_login_and_logout\": false",
+            )
+            .reply(
+                "print_repaired_json",
+                r#"{"is_crud_required": true, "is_user_login_and_logout": false, "is_external_urls_required": false}"#,
+            );
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = BasicAgent::new("Test".to_string(), "Solutions Architect".to_string());
+
+        let scope = ai_task_request_decoded::<ProjectScope>(
+            llm.as_ref(),
+            "a todo list api".to_string(),
+            &mut agent,
+            "print_project_scope",
+            print_project_scope,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert!(scope.is_crud_required);
+        assert_eq!(
+            server.calls(),
+            vec!["print_project_scope", "print_repaired_json"]
+        );
+
+        // The fix prompt carries the parse error and the bad output
+        let repair_prompt = server.requests()[1]["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(repair_prompt.contains("ERROR: EOF while parsing an object"));
+        assert!(repair_prompt.contains("INVALID_OUTPUT: Sure, here is the scope"));
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tests_routing_to_model_and_falling_back() {
+        let script = MockScript::new()
+            .fail("convert_user_input_to_goal", 503, "Model overloaded")
+            .reply("convert_user_input_to_goal", "build a todo list backend");
+
+        let server = MockLlmServer::start(script).await;
+        let mut config = server.config();
+        config.retry.max_attempts = 1;
+        config.routes.insert(
+            "convert_user_input_to_goal",
+            Route {
+                model: Some("gpt-4o".to_string()),
+                temperature: Some(0.0),
+                seed: Some(42),
+                stop: Some(vec!["\n\n".to_string()]),
+                fallback_models: vec!["gpt-4o-mini".to_string()],
+                ..Route::default()
+            },
+        );
+        let llm = create_llm_client(config).unwrap();
+        let mut agent = BasicAgent::new("Test".to_string(), "Managing Agent".to_string());
+
+        let goal = ai_task_request(
+            llm.as_ref(),
+            "a todo app".to_string(),
+            &mut agent,
+            "convert_user_input_to_goal",
+            convert_user_input_to_goal,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(goal, "build a todo list backend");
+
+        let requests = server.requests();
+        assert_eq!(requests[0]["model"], "gpt-4o");
+        assert_eq!(requests[0]["temperature"], 0.0);
+        assert_eq!(requests[0]["seed"], 42);
+        assert_eq!(requests[0]["stop"][0], "\n\n");
+        assert_eq!(requests[1]["model"], "gpt-4o-mini");
+        assert_eq!(llm.usage().report(&llm.config().prices).rows[0].calls, 1);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tests_sending_prompt_template_instead_of_built_in() {
+        let script = MockScript::new().reply("print_fixed_code", "fn main() {}");
+
+        let server = MockLlmServer::start(script).await;
+        let mut config = server.config();
+        config.prompts.insert(
+            "print_fixed_code",
+            "pub fn print_fixed_code() { /// Fix {{input}} using only axum }",
+        );
+        let llm = create_llm_client(config).unwrap();
+        let mut agent = BasicAgent::new("Test".to_string(), "Backend Developer".to_string());
+
+        ai_task_request(
+            llm.as_ref(),
+            "BROKEN_CODE: fn main() { broken }".to_string(),
+            &mut agent,
+            "print_fixed_code",
+            print_fixed_code,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let prompt = server.requests()[0]["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(prompt.contains("Fix BROKEN_CODE: fn main() { broken } using only axum"));
+        assert_eq!(
+            prompt.matches("BROKEN_CODE: fn main() { broken }").count(),
+            1
+        );
+        assert!(!prompt.contains("Removes bugs from code"));
+
+        server.stop().await;
     }
 }
@@ -0,0 +1,166 @@
+// Recovers JSON from LLM output that wraps it in prose or code fences,
+// or bends the syntax in ways models commonly do
+
+// Body of the first ``` fenced block, or the text as is when there is none
+pub fn strip_fences(text: &str) -> &str {
+    let Some(start) = text.find("```") else {
+        return text.trim();
+    };
+
+    // Skip the language tag on the opening fence
+    let after_open = &text[start + 3..];
+    let body = match after_open.find('\n') {
+        Some(newline) => &after_open[newline + 1..],
+        None => after_open,
+    };
+
+    match body.find("```") {
+        Some(end) => body[..end].trim(),
+        None => body.trim(),
+    }
+}
+
+// The first balanced JSON object or array, skipping brackets inside strings
+pub fn extract_json(text: &str) -> Option<&str> {
+    let start = text.find(['{', '['])?;
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + offset + c.len_utf8()]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Fix trailing commas, curly quotes and Python literals outside of strings
+pub fn repair_json(json: &str) -> String {
+    let json = json.replace(['\u{201C}', '\u{201D}'], "\"");
+
+    let mut repaired = String::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = json.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            repaired.push(c);
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                repaired.push(c);
+            }
+            // Drop a comma when only whitespace separates it from a closing bracket
+            ',' if json[index + 1..].trim_start().starts_with(['}', ']']) => {}
+            c if c.is_ascii_alphabetic() => {
+                let rest = &json[index..];
+                let word_len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let word = &rest[..word_len];
+
+                repaired.push_str(match word {
+                    "True" => "true",
+                    "False" => "false",
+                    "None" => "null",
+                    _ => word,
+                });
+
+                for _ in 1..word.chars().count() {
+                    chars.next();
+                }
+            }
+            _ => repaired.push(c),
+        }
+    }
+
+    repaired
+}
+
+// Each recovery step applied in turn, most faithful to the output first
+pub fn candidates(output: &str) -> Vec<String> {
+    let unfenced = strip_fences(output);
+    // Cut-off output has no balanced value, so keep everything after its start
+    let extracted = extract_json(unfenced)
+        .or_else(|| unfenced.find(['{', '[']).map(|start| &unfenced[start..]))
+        .unwrap_or(unfenced);
+
+    let mut candidates = vec![output.to_string()];
+    for candidate in [
+        unfenced.to_string(),
+        extracted.to_string(),
+        repair_json(extracted),
+    ] {
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_extracting_json_from_prose_and_fences() {
+        let output =
+            "Here are the urls:\n```json\n[\"https://api.binance.com\"]\n```\nLet me know!";
+        assert_eq!(strip_fences(output), "[\"https://api.binance.com\"]");
+
+        let prose =
+            r#"Sure! {"route": "/todo/{id}", "note": "use ] and } freely"} hope this helps"#;
+        assert_eq!(
+            extract_json(prose),
+            Some(r#"{"route": "/todo/{id}", "note": "use ] and } freely"}"#)
+        );
+        assert_eq!(extract_json("no json here"), None);
+    }
+
+    #[test]
+    fn tests_repairing_common_mistakes() {
+        let repaired =
+            repair_json("{\"is_crud_required\": True, \"urls\": [None, \u{201C}a, b,\u{201D},],}");
+
+        assert_eq!(
+            repaired,
+            r#"{"is_crud_required": true, "urls": [null, "a, b,"]}"#
+        );
+        assert_eq!(
+            candidates("```\n{\"a\": 1,}\n```").last().unwrap(),
+            "{\"a\": 1}"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// Applies generated SQL migrations to an in-memory SQLite database, so broken
+// ones are caught before any backend code is written against them
+
+use std::collections::BTreeSet;
+
+use rusqlite::{params_from_iter, Connection};
+
+use crate::models::agents::agent_traits::DataModel;
+
+// What is wrong with the migrations, empty when they apply cleanly and create
+// every entity with all of its fields
+pub fn check_migrations(data_model: &DataModel) -> Result<Vec<String>, rusqlite::Error> {
+    let connection = Connection::open_in_memory()?;
+    connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+
+    // Later migrations build on earlier ones, so stop at the first that fails
+    for migration in &data_model.migrations {
+        if let Err(e) = connection.execute_batch(&migration.sql) {
+            return Ok(vec![format!("{} failed to apply: {}", migration.name, e)]);
+        }
+    }
+
+    // SQLite names are case insensitive
+    let tables: BTreeSet<String> = query_names(
+        &connection,
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        None,
+    )?;
+
+    let mut errors = vec![];
+    for entity in &data_model.entities {
+        if !tables.contains(&entity.name.to_lowercase()) {
+            errors.push(format!("No table for entity {}", entity.name));
+            continue;
+        }
+
+        let columns = query_names(
+            &connection,
+            "SELECT name FROM pragma_table_info(?1)",
+            Some(&entity.name),
+        )?;
+        for field in &entity.fields {
+            if !columns.contains(&field.name.to_lowercase()) {
+                errors.push(format!("No column {}.{}", entity.name, field.name));
+            }
+        }
+    }
+
+    // Foreign keys are only enforced on writes, so a missing table would go unnoticed
+    for table in &tables {
+        let referenced = query_names(
+            &connection,
+            "SELECT \"table\" FROM pragma_foreign_key_list(?1)",
+            Some(table),
+        )?;
+        for missing in referenced.difference(&tables) {
+            errors.push(format!(
+                "Table {} references {}, which does not exist",
+                table, missing
+            ));
+        }
+    }
+
+    Ok(errors)
+}
+
+// First column of every row, lowercased
+fn query_names(
+    connection: &Connection,
+    sql: &str,
+    table: Option<&str>,
+) -> Result<BTreeSet<String>, rusqlite::Error> {
+    let mut statement = connection.prepare(sql)?;
+    let rows = statement.query_map(params_from_iter(table), |row| row.get::<_, String>(0))?;
+
+    rows.map(|name| name.map(|name| name.to_lowercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::{Entity, EntityField, Migration};
+
+    fn data_model(sql: &str) -> DataModel {
+        let field = |name: &str| EntityField {
+            name: name.to_string(),
+            sql_type: "INTEGER".to_string(),
+            is_primary_key: name == "id",
+            is_nullable: false,
+            references: None,
+        };
+
+        DataModel {
+            entities: vec![
+                Entity {
+                    name: "user".to_string(),
+                    fields: vec![field("id")],
+                },
+                Entity {
+                    name: "task".to_string(),
+                    fields: vec![field("id"), field("user_id"), field("completed")],
+                },
+            ],
+            migrations: vec![Migration {
+                name: "0001_initial_schema.sql".to_string(),
+                sql: sql.to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn tests_checking_migrations() {
+        let valid = data_model(
+            "CREATE TABLE user (id INTEGER PRIMARY KEY);
+            CREATE TABLE Task (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL,
+                completed INTEGER NOT NULL,
+                FOREIGN KEY (user_id) REFERENCES user (id)
+            );",
+        );
+        assert_eq!(check_migrations(&valid).unwrap(), Vec::<String>::new());
+
+        let incomplete = data_model(
+            "CREATE TABLE task (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES users (id)
+            );",
+        );
+        assert_eq!(
+            check_migrations(&incomplete).unwrap(),
+            [
+                "No table for entity user",
+                "No column task.completed",
+                "Table task references users, which does not exist",
+            ]
+        );
+
+        let broken = data_model("CREATE TABLE user (id INTEGER PRIMARY KEY,);");
+        let errors = check_migrations(&broken).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .starts_with("0001_initial_schema.sql failed to apply: near \")\": syntax error"));
+    }
+}
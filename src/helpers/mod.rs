@@ -1,2 +1,12 @@
+pub mod code_blocks;
 pub mod command_line;
+pub mod doc_checks;
+pub mod fetch_calls;
 pub mod generals;
+pub mod json_repair;
+pub mod migration_check;
+pub mod security_checks;
+pub mod test_results;
+#[cfg(test)]
+pub mod test_support;
+pub mod workspace;
@@ -0,0 +1,236 @@
+// Static checks on generated backend code, run before it is built and started
+
+use regex::Regex;
+
+use crate::{
+    apis::transcript::DEFAULT_SECRET_PATTERNS,
+    models::agents::agent_traits::{SecurityFinding, Severity},
+};
+
+const HARD_CODED_SECRET: &str = "hard-coded secret";
+
+// Lines after a web::Payload argument searched for a size check on the body
+const PAYLOAD_CHECK_WINDOW: usize = 20;
+
+// Checks that a single line gives away
+const LINE_CHECKS: [(Severity, &str, &str, &str); 7] = [
+    (
+        Severity::High,
+        HARD_CODED_SECRET,
+        r#"(?i)\b\w*(api_?key|secret|password|passwd|token)\w*\s*(:\s*&(?:'static\s+)?str\s*)?[:=]\s*"[^"]{4,}""#,
+        "read secrets from the environment instead",
+    ),
+    (
+        Severity::High,
+        "unsafe code",
+        r"\bunsafe\b",
+        "the web server has no need for unsafe",
+    ),
+    (
+        Severity::High,
+        "process execution",
+        r"\bstd::process\b|\bCommand::new\(",
+        "runs programs on the host",
+    ),
+    (
+        Severity::High,
+        "file access outside the project",
+        r#"(?:File::(?:open|create)|fs::\w+|\.open)\(\s*r?#?"(?:/|\\|[A-Za-z]:[\\/]|~|[^"]*\.\.[\\/])"#,
+        "only paths inside the project directory may be read or written",
+    ),
+    (
+        Severity::Medium,
+        "permissive CORS",
+        r"Cors::permissive\(\)|\.allow_any_origin\(\)|\.send_wildcard\(\)",
+        "any website can call the API",
+    ),
+    (
+        Severity::Medium,
+        "unbounded request body",
+        r"\.limit\(\s*usize::MAX\s*\)|PayloadConfig::new\(\s*usize::MAX\s*\)",
+        "request bodies have no size limit",
+    ),
+    (
+        Severity::Low,
+        "permissive CORS",
+        r"\.allow_any_(?:method|header)\(\)",
+        "any method or header is allowed cross-origin",
+    ),
+];
+
+// Every finding in the code, most severe first
+pub fn scan_backend_code(code: &str) -> Vec<SecurityFinding> {
+    let line_checks: Vec<(Severity, &str, Regex, &str)> = LINE_CHECKS
+        .iter()
+        .map(|(severity, check, pattern, message)| {
+            (
+                *severity,
+                *check,
+                Regex::new(pattern).expect("Valid regex"),
+                *message,
+            )
+        })
+        .collect();
+    let secret_formats: Vec<Regex> = DEFAULT_SECRET_PATTERNS
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("Valid regex"))
+        .collect();
+
+    let password_field = Regex::new(r"\bpassword\w*\s*:\s*String\b").expect("Valid regex");
+    // Comments blanked out so they neither raise findings nor count as a limit
+    let code_lines: Vec<&str> = code
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let is_comment =
+                trimmed.starts_with("//") || trimmed.starts_with("/*") || trimmed.starts_with('*');
+            if is_comment {
+                ""
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    // Real hashing crates, not HashMap or hash_map
+    let hashing_api = Regex::new(r"\b(?:bcrypt::|argon2::|Argon2\b|pbkdf2|scrypt::|sha2::)")
+        .expect("Valid regex");
+    let hashes_passwords = code_lines.iter().any(|line| hashing_api.is_match(line));
+
+    // A configured limit covers every extractor, as long as it is not usize::MAX
+    let limit_call = Regex::new(r"\bPayloadConfig::new\(|\.limit\(").expect("Valid regex");
+    let limits_payload = code_lines
+        .iter()
+        .any(|line| limit_call.is_match(line) && !line.contains("usize::MAX"));
+    let size_check = Regex::new(r"\.len\(\)[^;{]*>|<=?[^;{]*\.len\(\)").expect("Valid regex");
+
+    let mut findings = vec![];
+    let mut add = |severity, check: &str, line, message: &str| {
+        let finding = SecurityFinding {
+            severity,
+            check: check.to_string(),
+            line,
+            message: message.to_string(),
+        };
+        if !findings.contains(&finding) {
+            findings.push(finding);
+        }
+    };
+
+    for (index, line) in code_lines.iter().enumerate() {
+        let line_number = index + 1;
+        if line.is_empty() {
+            continue;
+        }
+
+        for (severity, check, pattern, message) in &line_checks {
+            if pattern.is_match(line) {
+                add(*severity, check, line_number, message);
+            }
+        }
+        if secret_formats.iter().any(|format| format.is_match(line)) {
+            add(
+                Severity::High,
+                HARD_CODED_SECRET,
+                line_number,
+                LINE_CHECKS[0].3,
+            );
+        }
+        if !hashes_passwords && password_field.is_match(line) {
+            add(
+                Severity::High,
+                "plaintext password",
+                line_number,
+                "passwords are stored without being hashed",
+            );
+        }
+        let checks_size = || {
+            code_lines[index..code_lines.len().min(index + PAYLOAD_CHECK_WINDOW)]
+                .iter()
+                .any(|line| size_check.is_match(line))
+        };
+        if !limits_payload && line.contains("web::Payload") && !checks_size() {
+            add(
+                Severity::Medium,
+                "unbounded request body",
+                line_number,
+                "the request body stream is read without a size limit",
+            );
+        }
+    }
+
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.line.cmp(&b.line)));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_scanning_backend_code() {
+        let code = r#"use actix_cors::Cors;
+use std::process::Command;
+
+const API_KEY: &str = "sk-abcdefghijklmnopqrstuvwx";
+
+#[derive(Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    pub password: String,
+}
+
+// unsafe is fine to mention in a comment
+fn load() -> String {
+    std::fs::read_to_string("../../etc/passwd").unwrap()
+}
+
+fn main() {
+    let cors = Cors::default().allow_any_origin().allow_any_method();
+    let data = fs::read_to_string("./database.json");
+}
+"#;
+
+        let findings: Vec<String> = scan_backend_code(code)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            findings,
+            [
+                "[HIGH] line 2: process execution: runs programs on the host",
+                "[HIGH] line 4: hard-coded secret: read secrets from the environment instead",
+                "[HIGH] line 9: plaintext password: passwords are stored without being hashed",
+                "[HIGH] line 14: file access outside the project: only paths inside the project directory may be read or written",
+                "[MEDIUM] line 18: permissive CORS: any website can call the API",
+                "[LOW] line 18: permissive CORS: any method or header is allowed cross-origin",
+            ]
+        );
+
+        let hashed =
+            "pub struct User {\n    pub password: String,\n}\nlet stored = bcrypt::hash(password);";
+        assert!(scan_backend_code(hashed).is_empty());
+
+        // An in-memory store is not hashing
+        let stored = "use std::collections::HashMap;\n\npub struct User {\n    pub password: String,\n}\n\n// TODO: bcrypt::hash the password\nstruct AppState {\n    users: Mutex<HashMap<u64, User>>,\n}";
+        assert_eq!(
+            scan_backend_code(stored)[0].to_string(),
+            "[HIGH] line 4: plaintext password: passwords are stored without being hashed"
+        );
+
+        // Talking about a limit is not setting one
+        let streamed = "// TODO: limit the body size\nasync fn upload(mut body: web::Payload) -> HttpResponse {\n    let rate_limit = 10;\n}";
+        assert_eq!(
+            scan_backend_code(streamed)[0].to_string(),
+            "[MEDIUM] line 2: unbounded request body: the request body stream is read without a size limit"
+        );
+        let configured = format!(
+            "{}\nApp::new().app_data(web::PayloadConfig::new(65_536));",
+            streamed
+        );
+        assert!(scan_backend_code(&configured).is_empty());
+        let checked = "async fn upload(mut body: web::Payload) -> HttpResponse {\n    while let Some(chunk) = body.next().await {\n        if bytes.len() + chunk.len() > MAX_SIZE {\n            return HttpResponse::PayloadTooLarge().finish();\n        }\n    }\n}";
+        assert!(scan_backend_code(checked).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+// Reads pass/fail results out of the libtest output `cargo test` prints
+
+use crate::models::agents::agent_traits::IntegrationTest;
+
+// Every test that ran, in the order reported, with the output of the failed ones.
+// Ignored tests are left out. Empty when the suite did not build.
+pub fn parse_test_output(stdout: &str) -> Vec<IntegrationTest> {
+    let mut tests: Vec<IntegrationTest> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("test ")?.rsplit_once(" ... "))
+        .filter_map(|(name, outcome)| {
+            let passed = match outcome.trim() {
+                "ok" => true,
+                "FAILED" => false,
+                _ => return None,
+            };
+
+            Some(IntegrationTest {
+                name: name.trim().to_string(),
+                passed,
+                failure: None,
+            })
+        })
+        .collect();
+
+    // Failed tests' output follows in "---- <name> stdout ----" sections
+    let mut current: Option<(String, Vec<&str>)> = None;
+    let mut sections = vec![];
+    for line in stdout.lines() {
+        let header = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"));
+
+        if header.is_some() || line == "failures:" {
+            sections.extend(current.take());
+        }
+        if let Some(name) = header {
+            current = Some((name.to_string(), vec![]));
+        } else if let Some((_, lines)) = &mut current {
+            if !line.starts_with("note: run with `RUST_BACKTRACE=1`") {
+                lines.push(line);
+            }
+        }
+    }
+    sections.extend(current);
+
+    for (name, lines) in sections {
+        if let Some(test) = tests.iter_mut().find(|test| test.name == name) {
+            test.failure = Some(lines.join("\n").trim().to_string());
+        }
+    }
+
+    tests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parsing_cargo_test_output() {
+        let stdout = r#"
+running 3 tests
+test tests_create_item ... ok
+test tests_delete_item ... FAILED
+test tests_slow ... ignored
+
+failures:
+
+---- tests_delete_item stdout ----
+thread 'tests_delete_item' panicked at tests/api_tests.rs:42:5:
+assertion `left == right` failed
+  left: 500
+ right: 200
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    tests_delete_item
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.02s
+"#;
+
+        assert_eq!(
+            parse_test_output(stdout),
+            [
+                IntegrationTest {
+                    name: "tests_create_item".to_string(),
+                    passed: true,
+                    failure: None,
+                },
+                IntegrationTest {
+                    name: "tests_delete_item".to_string(),
+                    passed: false,
+                    failure: Some(
+                        "thread 'tests_delete_item' panicked at tests/api_tests.rs:42:5:\nassertion `left == right` failed\n  left: 500\n right: 200"
+                            .to_string()
+                    ),
+                },
+            ]
+        );
+        assert!(parse_test_output("error[E0425]: cannot find value `client`").is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+// Generated-project fixtures for agent tests
+
+use std::{env, fs, net::TcpListener, ops::Deref};
+
+use super::workspace::Workspace;
+
+// A throwaway web server project, deleted when dropped so a failing test doesn't leave it behind
+#[derive(Debug)]
+pub struct ScratchWorkspace(Workspace);
+
+impl Deref for ScratchWorkspace {
+    type Target = Workspace;
+
+    fn deref(&self) -> &Workspace {
+        &self.0
+    }
+}
+
+impl Drop for ScratchWorkspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0.root);
+    }
+}
+
+// Starts without dependencies, so generated code builds offline
+pub fn scratch_workspace(name: &str) -> ScratchWorkspace {
+    let root = env::temp_dir().join(format!("web_server_{}_{}", name, std::process::id()));
+    let workspace = Workspace {
+        root,
+        port: free_port(),
+        confirm_before_run: false,
+    };
+
+    let cargo_toml = "[package]\nname = \"web_server\"\nversion = \"0.1.0\"\nedition = \"2021\"\n";
+    let template = "fn main() {}\n";
+
+    fs::create_dir_all(workspace.root.join("src").join("schemas"))
+        .expect("Failed to create scratch workspace");
+    fs::write(workspace.root.join("Cargo.toml"), cargo_toml).unwrap();
+    fs::write(workspace.code_template_path(), template).unwrap();
+    fs::write(workspace.main_path(), template).unwrap();
+
+    ScratchWorkspace(workspace)
+}
+
+// Std-only server the scripted LLM hands out as "generated" backend code.
+// It answers 200 to the given number of requests and then exits.
+pub fn scripted_server_code(port: u16, requests: usize) -> String {
+    format!(
+        r#"use std::io::{{Read, Write}};
+use std::net::TcpListener;
+
+fn main() {{
+    let listener = TcpListener::bind("127.0.0.1:{port}").unwrap();
+    for stream in listener.incoming().take({requests}) {{
+        let mut stream = stream.unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]");
+    }}
+}}
+"#
+    )
+}
+
+// A port nothing is listening on, so parallel tests and other processes don't clash
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port")
+        .port()
+}
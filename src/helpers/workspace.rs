@@ -0,0 +1,88 @@
+use std::{env, path::PathBuf};
+
+// Used when WEB_SERVER_PROJECT_PATH is not set
+const DEFAULT_PROJECT_PATH: &str = r#"D:\rust\web_server"#;
+const DEFAULT_PORT: u16 = 8080;
+
+// The generated web server project that agents write into, build and run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    pub root: PathBuf,
+    // Port the generated server listens on
+    pub port: u16,
+    // Ask the user before building and running generated code
+    pub confirm_before_run: bool,
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            port: DEFAULT_PORT,
+            confirm_before_run: true,
+        }
+    }
+
+    // WEB_SERVER_PROJECT_PATH and WEB_SERVER_PORT override the defaults
+    pub fn from_env() -> Self {
+        let root = env::var("WEB_SERVER_PROJECT_PATH")
+            .unwrap_or_else(|_| DEFAULT_PROJECT_PATH.to_string());
+
+        let mut workspace = Self::new(root);
+        if let Some(port) = env::var("WEB_SERVER_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+        {
+            workspace.port = port;
+        }
+
+        workspace
+    }
+
+    pub fn cargo_toml_path(&self) -> PathBuf {
+        self.root.join("Cargo.toml")
+    }
+
+    pub fn code_template_path(&self) -> PathBuf {
+        self.root.join("src").join("code_template.rs")
+    }
+
+    pub fn main_path(&self) -> PathBuf {
+        self.root.join("src").join("main.rs")
+    }
+
+    pub fn api_schema_path(&self) -> PathBuf {
+        self.root
+            .join("src")
+            .join("schemas")
+            .join("api_schema.json")
+    }
+
+    // Integration suite written by the QA engineer
+    pub fn integration_tests_path(&self) -> PathBuf {
+        self.root.join("tests").join("api_tests.rs")
+    }
+
+    // SQL migrations the generated server applies at startup
+    pub fn migrations_dir(&self) -> PathBuf {
+        self.root.join("migrations")
+    }
+
+    // Static frontend served from disk, calling the server at server_url
+    pub fn frontend_dir(&self) -> PathBuf {
+        self.root.join("frontend")
+    }
+
+    pub fn readme_path(&self) -> PathBuf {
+        self.root.join("README.md")
+    }
+
+    // Endpoint reference with curl examples, linked from the README
+    pub fn api_reference_path(&self) -> PathBuf {
+        self.root.join("docs").join("API.md")
+    }
+
+    pub fn server_url(&self, route: &str) -> String {
+        format!("http://localhost:{}{}", self.port, route)
+    }
+}
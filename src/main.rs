@@ -8,13 +8,76 @@ macro_rules! get_function_string {
 #[macro_use]
 mod ai_functions;
 mod apis;
+mod eval;
 mod helpers;
 mod models;
 
-use helpers::command_line::get_user_response;
+use std::{env, process};
+
+use apis::{
+    llm_client::create_llm_client, llm_config::LlmConfig, llm_error::LlmError,
+    response_cache::CacheMode,
+};
+use helpers::{
+    command_line::{get_user_response, run_eval_command, run_prompts_command},
+    workspace::Workspace,
+};
+use models::agent_manager::managing_agent::ManagingAgent;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // Inspecting prompts needs neither a project nor an API key
+    if args.first().is_some_and(|arg| arg == "prompts") {
+        let config = LlmConfig::read().expect("Failed to load LLM config");
+        if let Err(e) = run_prompts_command(&config.prompts, &args[1..]) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Score prompt variants against a corpus of project requests
+    if args.first().is_some_and(|arg| arg == "eval") {
+        if let Err(e) = run_eval_command(&args[1..]).await {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
 
-fn main() {
     let user_request = get_user_response("What webserver are we building today?");
 
-    println!("{}", user_request);
+    // Provider, model and endpoint come from llm.toml and the environment
+    let mut config = LlmConfig::load().expect("Failed to load LLM config");
+
+    // Command line flags win over the configured cache mode
+    for arg in &args {
+        match arg.as_str() {
+            "--no-cache" => config.cache.mode = CacheMode::Off,
+            "--refresh-cache" => config.cache.mode = CacheMode::Refresh,
+            _ => {}
+        }
+    }
+
+    // One client for the whole run, its session pools connections and rate limits all agents
+    let llm = create_llm_client(config).expect("Failed to create LLM client");
+
+    let workspace = Workspace::from_env();
+    let mut managing_agent =
+        match ManagingAgent::new(user_request, llm.clone(), workspace.clone()).await {
+            Ok(managing_agent) => managing_agent,
+            // Out of budget before the first agent, stop the same way a run out of budget does
+            Err(e) if matches!(e.downcast_ref(), Some(LlmError::BudgetExceeded(_))) => {
+                ManagingAgent::stop_before_start(llm, workspace, e.to_string());
+                return;
+            }
+            Err(e) => {
+                eprintln!("Error creating managing agent: {}", e);
+                process::exit(1);
+            }
+        };
+
+    managing_agent.execute_project().await;
 }
@@ -1,91 +1,451 @@
+use std::{fs, io, sync::Arc};
+
+use serde::Serialize;
+
 use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
+use crate::apis::llm_client::LlmClient;
+use crate::apis::llm_error::LlmError;
+use crate::helpers::command_line::PrintCommand;
 use crate::helpers::generals::ai_task_request;
+use crate::helpers::workspace::Workspace;
 use crate::models::agent_basic::basic_agent::AgentState;
 use crate::models::agents::agent_architect::AgentSolutionArchitect;
-use crate::models::general::llm::Message;
+use crate::models::agents::agent_backend::AgentBackendDeveloper;
+use crate::models::agents::agent_database::AgentDatabaseArchitect;
+use crate::models::agents::agent_docs::AgentTechnicalWriter;
+use crate::models::agents::agent_frontend::AgentFrontendDeveloper;
+use crate::models::agents::agent_qa::{describe_failures, AgentQaEngineer};
+use crate::models::general::llm::Usage;
 use crate::models::{
     agent_basic::basic_agent::BasicAgent,
     agents::agent_traits::{FactSheet, SpecialFunctions},
 };
 
+// Rounds of integration test failures sent back to the backend before the results are accepted
+const MAX_BACKEND_FIXES: u8 = 2;
+
+// Written when a budget stops the run, so the work done so far is not lost
+#[derive(Debug, Serialize)]
+struct Checkpoint<'a> {
+    reason: String,
+    completed_agents: Vec<String>,
+    usage: Usage,
+    factsheet: &'a FactSheet,
+}
+
 #[derive(Debug)]
 pub struct ManagingAgent {
     attributes: BasicAgent,
     factsheet: FactSheet,
     agents: Vec<Box<dyn SpecialFunctions>>,
+    // Where in agents the backend developer is, and the QA engineer testing its code
+    backend_index: Option<usize>,
+    qa_index: Option<usize>,
+    // Agents whose output no later agent needs, so the run goes on without it
+    optional_agents: Vec<usize>,
+    llm: Arc<dyn LlmClient>,
+    workspace: Workspace,
 }
 
 impl ManagingAgent {
-    pub async fn new(user_request: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let position = "Project Manager".to_string();
-        let attributes = BasicAgent {
-            objective: "Mange agents who are building an excellent website for the user"
-                .to_string(),
-            position: position.clone(),
-            state: AgentState::Discovery,
-            memory: vec![],
-        };
+    pub async fn new(
+        user_request: String,
+        llm: Arc<dyn LlmClient>,
+        workspace: Workspace,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut attributes = Self::manager_attributes();
 
         let project_description: String = ai_task_request(
+            llm.as_ref(),
             user_request,
-            &position,
+            &mut attributes,
             get_function_string!(convert_user_input_to_goal),
             convert_user_input_to_goal,
+            false,
         )
-        .await;
-
-        let agents: Vec<Box<dyn SpecialFunctions>> = vec![];
+        .await?;
 
         let factsheet = FactSheet {
             project_description,
-            project_scope: None,
-            external_urls: None,
-            backend_code: None,
-            api_endpoint_schema: None,
+            ..Default::default()
         };
 
-        Ok(Self {
+        Ok(Self::with_factsheet(attributes, factsheet, llm, workspace))
+    }
+
+    // For when the budget is used up before the request is even turned into a goal,
+    // e.g. max_cost_usd set to 0, so the run still ends with a checkpoint and its costs
+    pub fn stop_before_start(llm: Arc<dyn LlmClient>, workspace: Workspace, reason: String) {
+        let manager = Self::with_factsheet(
+            Self::manager_attributes(),
+            FactSheet::default(),
+            llm,
+            workspace,
+        );
+
+        manager.stop_for_budget(reason, vec![]);
+        manager.finish_run();
+    }
+
+    fn manager_attributes() -> BasicAgent {
+        BasicAgent {
+            objective: "Mange agents who are building an excellent website for the user"
+                .to_string(),
+            position: "Project Manager".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        }
+    }
+
+    fn with_factsheet(
+        attributes: BasicAgent,
+        factsheet: FactSheet,
+        llm: Arc<dyn LlmClient>,
+        workspace: Workspace,
+    ) -> Self {
+        Self {
             attributes,
-            agents,
+            agents: vec![],
+            backend_index: None,
+            qa_index: None,
+            optional_agents: vec![],
             factsheet,
-        })
+            llm,
+            workspace,
+        }
     }
 
-    fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>) {
+    fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>) -> usize {
         self.agents.push(agent);
+        self.agents.len() - 1
     }
 
     fn create_agents(&mut self) {
-        self.add_agent(Box::new(AgentSolutionArchitect::new()));
+        self.add_agent(Box::new(AgentSolutionArchitect::new(self.llm.clone())));
+        self.add_agent(Box::new(AgentDatabaseArchitect::new(
+            self.llm.clone(),
+            self.workspace.clone(),
+        )));
+        self.backend_index = Some(self.add_agent(Box::new(AgentBackendDeveloper::new(
+            self.llm.clone(),
+            self.workspace.clone(),
+        ))));
+        self.qa_index = Some(self.add_agent(Box::new(AgentQaEngineer::new(
+            self.llm.clone(),
+            self.workspace.clone(),
+        ))));
+        let frontend_index = self.add_agent(Box::new(AgentFrontendDeveloper::new(
+            self.llm.clone(),
+            self.workspace.clone(),
+        )));
+        self.optional_agents.push(frontend_index);
+        // Runs last, documenting what the others built
+        self.add_agent(Box::new(AgentTechnicalWriter::new(
+            self.llm.clone(),
+            self.workspace.clone(),
+        )));
     }
 
     pub async fn execute_project(&mut self) {
         self.create_agents();
 
-        for agent in &mut self.agents {
-            let agent_result: Result<(), Box<dyn std::error::Error>> =
-                agent.execute(&mut self.factsheet).await;
+        let mut completed_agents: Vec<String> = vec![];
+        let mut budget_error: Option<String> = None;
 
-            let agent_info = agent.get_attributes_from_agent();
+        for index in 0..self.agents.len() {
+            // Don't start an agent the budget can no longer pay for
+            if let Err(e) = self
+                .llm
+                .config()
+                .budget
+                .check(self.llm.usage(), &self.llm.config().prices)
+            {
+                budget_error = Some(e.to_string());
+                break;
+            }
+
+            let mut agent_result: Result<(), Box<dyn std::error::Error>> =
+                self.agents[index].execute(&mut self.factsheet).await;
+            if agent_result.is_ok() && Some(index) == self.qa_index {
+                agent_result = self.fix_integration_failures().await;
+            }
+
+            let agent_info = self.agents[index].get_attributes_from_agent();
             dbg!(agent_info);
+
+            if let Err(e) = agent_result {
+                if let Some(LlmError::BudgetExceeded(_)) = e.downcast_ref::<LlmError>() {
+                    budget_error = Some(e.to_string());
+                    break;
+                }
+
+                PrintCommand::Issue
+                    .print_agent_message(&agent_info.position, &format!("Agent failed: {}", e));
+
+                // Later agents build on this one's output, so stop here
+                match self.optional_agents.contains(&index) {
+                    true => continue,
+                    false => break,
+                }
+            }
+
+            completed_agents.push(agent_info.position.clone());
+        }
+
+        if let Some(reason) = budget_error {
+            self.stop_for_budget(reason, completed_agents);
+        }
+
+        self.finish_run();
+    }
+
+    // Costs and the transcript location, however the run ended
+    fn finish_run(&self) {
+        self.print_cost_breakdown();
+
+        if let Some(path) = self.llm.transcript().path() {
+            PrintCommand::AICall.print_agent_message(
+                &self.attributes.position,
+                &format!("Transcript saved to {}", path.display()),
+            );
         }
     }
+
+    // Failing integration tests go back to the backend developer that wrote the code,
+    // so its fix builds on its earlier attempts, then QA tests the fix
+    async fn fix_integration_failures(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (Some(backend_index), Some(qa_index)) = (self.backend_index, self.qa_index) else {
+            return Ok(());
+        };
+
+        for _ in 0..MAX_BACKEND_FIXES {
+            let Some(failures) = describe_failures(&self.factsheet) else {
+                return Ok(());
+            };
+
+            self.agents[backend_index].report_bugs(failures);
+            self.agents[backend_index]
+                .execute(&mut self.factsheet)
+                .await?;
+            self.agents[qa_index].execute(&mut self.factsheet).await?;
+        }
+
+        // The results stay on the factsheet for whoever picks up from here
+        if describe_failures(&self.factsheet).is_some() {
+            PrintCommand::Issue.print_agent_message(
+                &self.attributes.position,
+                &format!(
+                    "Integration tests still failing after {} backend fixes",
+                    MAX_BACKEND_FIXES
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn stop_for_budget(&self, reason: String, completed_agents: Vec<String>) {
+        let path = &self.llm.config().budget.checkpoint_path;
+
+        PrintCommand::Issue.print_agent_message(
+            &self.attributes.position,
+            &format!("Stopping run, {}", reason),
+        );
+
+        match self.save_checkpoint(path, reason, completed_agents) {
+            Ok(()) => PrintCommand::AICall.print_agent_message(
+                &self.attributes.position,
+                &format!("Checkpoint saved to {}", path),
+            ),
+            Err(e) => PrintCommand::Issue.print_agent_message(
+                &self.attributes.position,
+                &format!("Failed to save checkpoint to {}: {}", path, e),
+            ),
+        }
+    }
+
+    fn save_checkpoint(
+        &self,
+        path: &str,
+        reason: String,
+        completed_agents: Vec<String>,
+    ) -> io::Result<()> {
+        let checkpoint = Checkpoint {
+            reason,
+            completed_agents,
+            usage: self.llm.usage().total(),
+            factsheet: &self.factsheet,
+        };
+
+        let contents = serde_json::to_string_pretty(&checkpoint)?;
+        fs::write(path, contents)
+    }
+
+    // Tokens and estimated spend of every LLM call made during the run
+    fn print_cost_breakdown(&self) {
+        let report = self.llm.usage().report(&self.llm.config().prices);
+
+        PrintCommand::AICall.print_agent_message(
+            &self.attributes.position,
+            &format!("Run cost breakdown (model: {})", self.llm.config().model),
+        );
+        println!("{}", report);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        apis::{
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+        },
+        helpers::test_support::{scratch_workspace, scripted_server_code},
+        models::agents::agent_traits::IntegrationTest,
+    };
 
     #[tokio::test]
     async fn tests_managing_agent() {
         let user_request = "Need a full application app that tracks my fitness progress Needs to include timezones";
 
-        let mut managing_agent = ManagingAgent::new(user_request.to_string())
-            .await
-            .expect("Error creating managing agent");
+        let workspace = scratch_workspace("managing_agent");
+        let server_code = scripted_server_code(workspace.port, 1);
+        // The integration test fails until the backend has fixed the code
+        let fixed_code = format!("{}// fixed\n", server_code);
+        // The frontend never stops calling a route the backend lacks
+        let frontend_code = format!(
+            "```html\n<script src=\"app.js\"></script>\n```\n```js\nfetch(\"{}\");\nfetch(\"{}\");\n```\n```css\nbody {{}}\n```",
+            workspace.server_url("/progress"),
+            workspace.server_url("/goals")
+        );
+
+        let script = MockScript::new()
+            .reply(
+                "convert_user_input_to_goal",
+                "build a website that tracks fitness progress with timezone support",
+            )
+            .reply(
+                "print_project_scope",
+                r#"{"is_crud_required": true, "is_user_login_and_logout": false, "is_external_urls_required": false}"#,
+            )
+            .reply(
+                "print_data_model",
+                r#"[{"name": "progress", "fields": [{"name": "id", "sql_type": "INTEGER", "is_primary_key": true, "is_nullable": false, "references": null}]}]"#,
+            )
+            .reply(
+                "print_sql_migrations",
+                "CREATE TABLE progress (id INTEGER PRIMARY KEY);",
+            )
+            .reply("print_backend_webserver_code", &server_code)
+            .reply("print_improved_webserver_code", &server_code)
+            .reply(
+                "print_rest_api_endpoints",
+                r#"[{"is_route_dyanmic": "false", "method": "get", "request_body": null, "route": "/progress"}]"#,
+            )
+            .reply(
+                "print_integration_tests",
+                "#[test]\nfn tests_server_code_was_fixed() {\n    assert!(std::fs::read_to_string(\"src/main.rs\").unwrap().contains(\"// fixed\"));\n}\n",
+            )
+            .reply("print_fixed_code", &fixed_code)
+            .reply("print_frontend_code", &frontend_code)
+            .reply("print_fixed_frontend_code", &frontend_code)
+            .reply(
+                "print_readme",
+                "# Fitness tracker\n\nRun it with `cargo run`.\n",
+            );
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+
+        let mut managing_agent =
+            ManagingAgent::new(user_request.to_string(), llm, workspace.clone())
+                .await
+                .expect("Error creating managing agent");
 
         managing_agent.execute_project().await;
 
-        dbg!(managing_agent.factsheet);
+        dbg!(&managing_agent.factsheet);
+
+        assert!(
+            managing_agent
+                .factsheet
+                .project_scope
+                .unwrap()
+                .is_crud_required
+        );
+        assert_eq!(managing_agent.factsheet.backend_code, Some(fixed_code));
+        assert!(managing_agent.factsheet.data_model.is_some());
+        assert_eq!(
+            managing_agent.factsheet.integration_tests,
+            Some(vec![IntegrationTest {
+                name: "tests_server_code_was_fixed".to_string(),
+                passed: true,
+                failure: None,
+            }])
+        );
+
+        // The failure went to the backend that wrote the code, along with its earlier attempts
+        let calls = server.calls();
+        assert_eq!(calls.len(), 14);
+        let fix = calls
+            .iter()
+            .position(|call| call == "print_fixed_code")
+            .unwrap();
+        assert_eq!(calls[fix - 1], "print_integration_tests");
+        let fix_request = &server.requests()[fix];
+        assert!(fix_request
+            .to_string()
+            .contains("Integration test tests_server_code_was_fixed failed"));
+        assert!(fix_request["messages"].as_array().unwrap().len() > 1);
+
+        // The failed frontend does not keep the docs from being written
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| *call == "print_fixed_frontend_code")
+                .count(),
+            2
+        );
+        assert_eq!(calls.last().unwrap(), "print_readme");
+        assert!(workspace.frontend_dir().join("index.html").exists());
+        assert!(workspace.readme_path().exists());
+        assert!(workspace.api_reference_path().exists());
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tests_stopping_before_the_first_agent() {
+        let workspace = scratch_workspace("managing_agent_no_budget");
+        let checkpoint_path = workspace.root.join("checkpoint.json");
+
+        let server = MockLlmServer::start(MockScript::new()).await;
+        let mut config = server.config();
+        config.budget.max_cost_usd = Some(0.0);
+        config.budget.checkpoint_path = checkpoint_path.display().to_string();
+        let llm = create_llm_client(config).unwrap();
+
+        let error = ManagingAgent::new(
+            "build a todo app".to_string(),
+            llm.clone(),
+            workspace.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(LlmError::BudgetExceeded(_))
+        ));
+
+        ManagingAgent::stop_before_start(llm, workspace.clone(), error.to_string());
+
+        let checkpoint: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&checkpoint_path).unwrap()).unwrap();
+        assert_eq!(checkpoint["reason"], error.to_string());
+        assert_eq!(checkpoint["completed_agents"], serde_json::json!([]));
+        assert!(server.calls().is_empty());
+
+        server.stop().await;
     }
 }
@@ -1,10 +1,11 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use reqwest::Client;
 
 use crate::{
     ai_functions::aifunc_architect::{print_project_scope, print_site_urls},
+    apis::{llm_client::LlmClient, llm_error::LlmError},
     helpers::{
         command_line::PrintCommand,
         generals::{ai_task_request_decoded, check_status_code},
@@ -20,10 +21,11 @@ use super::agent_traits::{FactSheet, ProjectScope, SpecialFunctions};
 #[derive(Debug)]
 pub struct AgentSolutionArchitect {
     attributes: BasicAgent,
+    llm: Arc<dyn LlmClient>,
 }
 
 impl AgentSolutionArchitect {
-    pub fn new() -> Self {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
         let attributes = BasicAgent {
             objective: "Gathers information and design solution for webserver development"
                 .to_string(),
@@ -32,42 +34,51 @@ impl AgentSolutionArchitect {
             memory: vec![],
         };
 
-        Self { attributes }
+        Self { attributes, llm }
     }
 
     // Retrive projects scope
-    async fn call_project_scope(&mut self, factsheet: &mut FactSheet) -> ProjectScope {
-        let msg_context = format!("{}", factsheet.project_description);
+    async fn call_project_scope(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<ProjectScope, LlmError> {
+        let msg_context = factsheet.project_description.to_string();
 
         let ai_response = ai_task_request_decoded::<ProjectScope>(
+            self.llm.as_ref(),
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_project_scope),
             print_project_scope,
+            false,
         )
-        .await;
+        .await?;
 
-        factsheet.project_scope = Some(ai_response.clone());
+        factsheet.project_scope = Some(ai_response);
         self.attributes.update_state(AgentState::Finished);
 
-        ai_response
+        Ok(ai_response)
     }
 
     async fn call_determine_extenal_urls(
         &mut self,
         factsheet: &mut FactSheet,
         msg_context: String,
-    ) {
+    ) -> Result<(), LlmError> {
         let ai_response = ai_task_request_decoded::<Vec<String>>(
+            self.llm.as_ref(),
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_site_urls),
             print_site_urls,
+            false,
         )
-        .await;
+        .await?;
 
         factsheet.external_urls = Some(ai_response);
-        self.attributes.state = AgentState::UnitTesting
+        self.attributes.state = AgentState::UnitTesting;
+
+        Ok(())
     }
 }
 
@@ -85,7 +96,7 @@ impl SpecialFunctions for AgentSolutionArchitect {
         while self.attributes.state != AgentState::Finished {
             match self.attributes.state {
                 AgentState::Discovery => {
-                    let project_scope = self.call_project_scope(factsheet).await;
+                    let project_scope = self.call_project_scope(factsheet).await?;
 
                     // Confirm if external urls
                     if project_scope.is_external_urls_required {
@@ -93,7 +104,7 @@ impl SpecialFunctions for AgentSolutionArchitect {
                             factsheet,
                             factsheet.project_description.clone(),
                         )
-                        .await;
+                        .await?;
                         self.attributes.state = AgentState::UnitTesting;
                     }
                 }
@@ -133,13 +144,13 @@ impl SpecialFunctions for AgentSolutionArchitect {
 
                     // Exclude any faulty urls
 
-                    if exclude_urls.len() > 0 {
+                    if !exclude_urls.is_empty() {
                         let new_urls: Vec<String> = factsheet
                             .external_urls
                             .as_ref()
                             .unwrap()
                             .iter()
-                            .filter(|url| !exclude_urls.contains(&url))
+                            .filter(|url| !exclude_urls.contains(url))
                             .cloned()
                             .collect();
 
@@ -163,17 +174,19 @@ impl SpecialFunctions for AgentSolutionArchitect {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::apis::{
+        cassette::cassette_client,
+        llm_client::create_llm_client,
+        mock_llm::{MockLlmServer, MockScript},
+    };
 
     #[tokio::test]
     async fn tests_solution_architect() {
-        let mut agent = AgentSolutionArchitect::new();
+        let mut agent = AgentSolutionArchitect::new(cassette_client("solution_architect"));
 
-        let mut factsheet = FactSheet{
+        let mut factsheet = FactSheet {
             project_description: "Build a full stack website with user login and logout that shows the latest Forex prices".to_string(),
-            project_scope: None,
-            external_urls: None,
-            backend_code: None,
-            api_endpoint_schema: None,
+            ..Default::default()
         };
 
         agent
@@ -185,4 +198,37 @@ mod tests {
         assert!(factsheet.external_urls.is_some());
         dbg!(agent);
     }
+
+    #[tokio::test]
+    async fn tests_failing_on_malformed_project_scope() {
+        let script = MockScript::new()
+            .reply(
+                "print_project_scope",
+                r#"{"is_crud_required": true, "is_user_login_and_logout": fal"#,
+            )
+            .reply("print_repaired_json", "I cannot tell what the value was");
+
+        let server = MockLlmServer::start(script).await;
+        let mut agent = AgentSolutionArchitect::new(create_llm_client(server.config()).unwrap());
+
+        let mut factsheet = FactSheet {
+            project_description: "Build a todo list api".to_string(),
+            ..Default::default()
+        };
+
+        let err = agent.execute(&mut factsheet).await.unwrap_err();
+
+        assert!(err.to_string().starts_with("Failed to decode LLM response"));
+        assert!(factsheet.project_scope.is_none());
+        assert_eq!(
+            server.calls(),
+            vec![
+                "print_project_scope",
+                "print_repaired_json",
+                "print_repaired_json"
+            ]
+        );
+
+        server.stop().await;
+    }
 }
@@ -2,32 +2,50 @@ use crate::ai_functions::aifunc_backend::{
     print_backend_webserver_code, print_fixed_code, print_improved_webserver_code,
     print_rest_api_endpoints,
 };
+use crate::apis::{llm_client::LlmClient, llm_error::LlmError};
+use crate::helpers::code_blocks::extract_rust_code;
 use crate::helpers::generals::{
-    ai_task_request_decoded, check_status_code, read_code_template_contents,
-    read_exec_main_contents, save_api_endpoints, save_backend_code, WEB_SERVER_PROJECT_PATH,
+    check_status_code, read_code_template_contents, read_exec_main_contents, save_api_endpoints,
+    save_backend_code,
 };
+use crate::helpers::workspace::Workspace;
 
 use crate::helpers::command_line::{confirm_safe_code, PrintCommand};
-use crate::helpers::generals::ai_task_request;
+use crate::helpers::generals::{ai_task_request, ai_task_request_decoded};
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_security::AgentSecurityReviewer;
 use crate::models::agents::agent_traits::{FactSheet, RouteObject, SpecialFunctions};
 
 use async_trait::async_trait;
 use reqwest::Client;
-use std::fs;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
 
+// What unit testing found in the generated server, read by the eval harness
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BackendTestReport {
+    pub build_failures: u32,
+    pub built: bool,
+    pub endpoints_checked: u32,
+    pub endpoints_passed: u32,
+}
+
 #[derive(Debug)]
 pub struct AgentBackendDeveloper {
     attributes: BasicAgent,
+    llm: Arc<dyn LlmClient>,
+    workspace: Workspace,
     bug_errors: Option<String>,
     bug_count: u8,
+    test_report: BackendTestReport,
+    // High severity findings stop the code from being built and run
+    security_reviewer: AgentSecurityReviewer,
 }
 
 impl AgentBackendDeveloper {
-    pub fn new() -> Self {
+    pub fn new(llm: Arc<dyn LlmClient>, workspace: Workspace) -> Self {
         let attributes = BasicAgent {
             objective: "Develops the backend code for the web sever and json database".to_string(),
             position: "Backend Developer".to_string(),
@@ -37,95 +55,159 @@ impl AgentBackendDeveloper {
 
         Self {
             attributes,
+            llm,
+            workspace,
             bug_errors: None,
             bug_count: 0,
+            test_report: BackendTestReport::default(),
+            security_reviewer: AgentSecurityReviewer::new(),
         }
     }
 
-    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) {
-        let code_template_str = read_code_template_contents();
+    pub fn test_report(&self) -> &BackendTestReport {
+        &self.test_report
+    }
+
+    async fn call_initial_backend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), LlmError> {
+        let code_template_str = read_code_template_contents(&self.workspace);
 
         // Concat instructions
-        let msg_context = format!(
-            "CODE TEMPLATE: {} \n PROJEC_DESCRIPTION: {} \n",
-            code_template_str, factsheet.project_description
+        // The template listens on 8080, the server has to listen where it will be checked
+        let mut msg_context = format!(
+            "CODE TEMPLATE: {} \n PROJEC_DESCRIPTION: {} \n PORT: {} \n",
+            code_template_str, factsheet.project_description, self.workspace.port
         );
 
+        // Code is written against the migrations the database architect checked
+        msg_context.push_str(&migrations_context(factsheet));
+
         let ai_response = ai_task_request(
+            self.llm.as_ref(),
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_backend_webserver_code),
             print_backend_webserver_code,
+            false,
         )
-        .await;
+        .await?;
+
+        self.save_generated_code(factsheet, &ai_response);
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        Ok(())
     }
 
-    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) {
+    async fn call_improved_backend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), LlmError> {
         // Concat instructions
-        let msg_context = format!(
-            "CODE TEMPLATE: {:?} \n PROJEC_DESCRIPTION: {:?} \n",
-            factsheet.backend_code, factsheet
+        let mut msg_context = format!(
+            "CODE TEMPLATE: {:?} \n PROJEC_DESCRIPTION: {:?} \n PORT: {} \n",
+            factsheet.backend_code, factsheet, self.workspace.port
         );
+        msg_context.push_str(&migrations_context(factsheet));
 
         let ai_response = ai_task_request(
+            self.llm.as_ref(),
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_improved_webserver_code),
-            print_backend_webserver_code,
+            print_improved_webserver_code,
+            true,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        self.save_generated_code(factsheet, &ai_response);
+
+        Ok(())
     }
 
-    async fn call_fixed_code_bugs(&mut self, factsheet: &mut FactSheet) {
+    async fn call_fixed_code_bugs(&mut self, factsheet: &mut FactSheet) -> Result<(), LlmError> {
         // Concat instructions
         let msg_context = format!(
-            "BROKEN_CODE: {:?} \n ERROR_BUGS: {:?} \n
+            "BROKEN_CODE: {:?} \n ERROR_BUGS: {:?} \n {}
             THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
-            factsheet.backend_code, self.bug_errors,
+            factsheet.backend_code,
+            self.bug_errors,
+            migrations_context(factsheet),
         );
 
         let ai_response = ai_task_request(
+            self.llm.as_ref(),
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_fixed_code),
-            print_backend_webserver_code,
+            print_fixed_code,
+            true,
         )
-        .await;
+        .await?;
+
+        self.save_generated_code(factsheet, &ai_response);
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        Ok(())
     }
 
-    async fn call_extract_rest_api_endpoints(&self) -> String {
-        let backend_code = read_exec_main_contents();
+    // Fences and commentary around the code would break the build, so only the code is saved
+    fn save_generated_code(&self, factsheet: &mut FactSheet, ai_response: &str) {
+        let extracted = extract_rust_code(ai_response);
+
+        if let Some(warning) = &extracted.warning {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), warning);
+        }
+
+        save_backend_code(&self.workspace, &extracted.code);
+        factsheet.backend_code = Some(extracted.code);
+    }
+
+    async fn call_extract_rest_api_endpoints(&mut self) -> Result<Vec<RouteObject>, LlmError> {
+        let backend_code = read_exec_main_contents(&self.workspace);
 
         // Structure our message context
         let msg_context = format!("CODE_INPUT: {}", backend_code);
 
-        let ai_response = ai_task_request(
+        ai_task_request_decoded::<Vec<RouteObject>>(
+            self.llm.as_ref(),
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_rest_api_endpoints),
-            print_backend_webserver_code,
+            print_rest_api_endpoints,
+            false,
         )
-        .await;
-
-        ai_response
+        .await
     }
 }
 
+// The migrations to write against, which also allow rusqlite. Empty without a database.
+fn migrations_context(factsheet: &FactSheet) -> String {
+    let Some(data_model) = &factsheet.data_model else {
+        return String::new();
+    };
+
+    let migrations: String = data_model
+        .migrations
+        .iter()
+        .map(|migration| format!("migrations/{}:\n{}\n", migration.name, migration.sql))
+        .collect();
+    format!("MIGRATIONS: {} \n", migrations)
+}
+
 #[async_trait]
 impl SpecialFunctions for AgentBackendDeveloper {
     fn get_attributes_from_agent(&self) -> &BasicAgent {
         &self.attributes
     }
 
+    // Bugs found outside of unit testing, e.g. failing integration tests.
+    // The next execute fixes them and unit tests the result again.
+    fn report_bugs(&mut self, bug_errors: String) {
+        self.bug_errors = Some(bug_errors);
+        self.bug_count = 1;
+        self.attributes.state = AgentState::Working;
+    }
+
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
@@ -133,25 +215,51 @@ impl SpecialFunctions for AgentBackendDeveloper {
         while self.attributes.state != AgentState::Finished {
             match &self.attributes.state {
                 AgentState::Discovery => {
-                    self.call_initial_backend_code(factsheet).await;
+                    self.call_initial_backend_code(factsheet).await?;
                     self.attributes.state = AgentState::Working;
                 }
                 AgentState::Working => {
                     if self.bug_count == 0 {
-                        self.call_improved_backend_code(factsheet).await;
+                        self.call_improved_backend_code(factsheet).await?;
                     } else {
-                        self.call_fixed_code_bugs(factsheet).await;
+                        self.call_fixed_code_bugs(factsheet).await?;
                     }
                     self.attributes.state = AgentState::UnitTesting;
                 }
                 AgentState::UnitTesting => {
+                    // Static review before anyone is asked to run the code
+                    let blocking = self.security_reviewer.review(factsheet);
+                    if !blocking.is_empty() {
+                        self.bug_count += 1;
+                        self.bug_errors = Some(
+                            blocking
+                                .iter()
+                                .map(|finding| format!("Security review: {}\n", finding))
+                                .collect(),
+                        );
+
+                        // Exit if too many bugs
+                        if self.bug_count > 2 {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Backend Code Unit Testing: High severity security findings remain",
+                            );
+
+                            return Err("High severity security findings in backend code".into());
+                        }
+
+                        // Pass back for rework
+                        self.attributes.state = AgentState::Working;
+                        continue;
+                    }
+
                     // ! Guard :: ENSURE AI SAFETY
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
                         "Backend code unit testing: Requesting user input",
                     );
 
-                    let is_safe_code = confirm_safe_code();
+                    let is_safe_code = !self.workspace.confirm_before_run || confirm_safe_code();
 
                     if !is_safe_code {
                         panic!("Better go work on some AI alignment instead...")
@@ -166,7 +274,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                     // Build code
                     let build_backend_server: std::process::Output = Command::new("cargo")
                         .arg("build")
-                        .current_dir(WEB_SERVER_PROJECT_PATH)
+                        .current_dir(&self.workspace.root)
                         .stdout(Stdio::piped())
                         .stderr(Stdio::piped())
                         .output()
@@ -175,6 +283,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                     // Determine if build errors
                     if build_backend_server.status.success() {
                         self.bug_count = 0;
+                        self.test_report.built = true;
 
                         PrintCommand::UnitTest.print_agent_message(
                             self.attributes.position.as_str(),
@@ -187,6 +296,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         // Update error stats
                         self.bug_count += 1;
                         self.bug_errors = Some(error_str);
+                        self.test_report.build_failures += 1;
 
                         // Exit if too many bugs
 
@@ -196,7 +306,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                                 "Backend Code Unit Testing: Too many bugs found in code",
                             );
 
-                            panic!("Error: Too many bugs");
+                            return Err("Too many bugs found in backend code".into());
                         }
 
                         // Pass back for rework
@@ -209,12 +319,8 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         Rest API endpoints
                     */
 
-                    let api_endpoints_str = self.call_extract_rest_api_endpoints().await;
-
-                    // Convert API endpoints into values
                     let api_enpoints: Vec<RouteObject> =
-                        serde_json::from_str(api_endpoints_str.as_str())
-                            .expect("Failed to decode API endpoints");
+                        self.call_extract_rest_api_endpoints().await?;
 
                     // Define endpoints to check
                     let check_endpoints: Vec<RouteObject> = api_enpoints
@@ -225,8 +331,8 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         .cloned()
                         .collect();
 
-                    // Store API Endpoints
-                    factsheet.api_endpoint_schema = Some(check_endpoints.clone());
+                    // Store API Endpoints, all of them for the agents that follow
+                    factsheet.api_endpoint_schema = Some(api_enpoints.clone());
 
                     // Run Backend application
                     PrintCommand::UnitTest.print_agent_message(
@@ -237,7 +343,7 @@ impl SpecialFunctions for AgentBackendDeveloper {
                     // Execute running server
                     let mut run_backend_server: std::process::Child = Command::new("cargo")
                         .arg("run")
-                        .current_dir(WEB_SERVER_PROJECT_PATH)
+                        .current_dir(&self.workspace.root)
                         .stdout(Stdio::piped())
                         .stderr(Stdio::piped())
                         .spawn()
@@ -269,10 +375,13 @@ impl SpecialFunctions for AgentBackendDeveloper {
                             .unwrap();
 
                         // Test url
-                        let url = format!("http://localhost:8080{}", endpoint.route);
+                        let url = self.workspace.server_url(&endpoint.route);
+                        self.test_report.endpoints_checked += 1;
                         match check_status_code(&client, &url).await {
                             Ok(status_code) => {
-                                if status_code != 200 {
+                                if status_code == 200 {
+                                    self.test_report.endpoints_passed += 1;
+                                } else {
                                     let err_msg = format!(
                                         "WARNING: Failed to call backend url endpont {}",
                                         endpoint.route
@@ -299,7 +408,9 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         }
                     }
 
-                    save_api_endpoints(&api_endpoints_str);
+                    let api_endpoints_str = serde_json::to_string_pretty(&api_enpoints)
+                        .expect("Route objects are always serializable");
+                    save_api_endpoints(&self.workspace, &api_endpoints_str);
 
                     PrintCommand::Issue.print_agent_message(
                         self.attributes.position.as_str(),
@@ -309,6 +420,8 @@ impl SpecialFunctions for AgentBackendDeveloper {
                     run_backend_server
                         .kill()
                         .expect("Failed to kill backend web server on completion");
+                    // Reap the killed process so it doesn't linger as a zombie
+                    let _ = run_backend_server.wait();
 
                     self.attributes.state = AgentState::Finished;
                 }
@@ -323,10 +436,45 @@ impl SpecialFunctions for AgentBackendDeveloper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        apis::{
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+        },
+        helpers::test_support::{scratch_workspace, scripted_server_code},
+    };
 
     #[tokio::test]
     async fn tests_writing_backend_code() {
-        let mut agent = AgentBackendDeveloper::new();
+        let workspace = scratch_workspace("backend_developer");
+        let server_code = scripted_server_code(workspace.port, 1);
+
+        // The improved code is cut off mid-function, so the first build fails.
+        // The fix comes back fenced and has to be unwrapped before it builds.
+        let script = MockScript::new()
+            .reply("print_backend_webserver_code", &server_code)
+            .reply(
+                "print_improved_webserver_code",
+                &server_code[..server_code.len() / 2],
+            )
+            .reply(
+                "print_fixed_code",
+                &format!("Here is the fixed code:\n```rust\n{}```\n", server_code),
+            )
+            .reply(
+                "print_rest_api_endpoints",
+                r#"Here are the endpoints:
+```json
+[
+    {"is_route_dynamic": "false", "method": "get", "request_body": null, "route": "/workouts"},
+    {"is_route_dynamic": "true", "method": "get", "request_body": null, "route": "/workouts/{id}"},
+]
+```"#,
+            );
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = AgentBackendDeveloper::new(llm, workspace.clone());
 
         let factsheet_str = r#"            
         {
@@ -348,5 +496,34 @@ mod tests {
             .execute(&mut factsheet)
             .await
             .expect("Failed to execute backend developer agent");
+
+        assert_eq!(
+            server.calls(),
+            vec![
+                "print_backend_webserver_code",
+                "print_improved_webserver_code",
+                "print_fixed_code",
+                "print_rest_api_endpoints"
+            ]
+        );
+        assert_eq!(factsheet.backend_code, Some(server_code));
+        // Scratch workspaces never use the template's 8080
+        let port_line = format!("PORT: {}", workspace.port);
+        for request in &server.requests()[..2] {
+            assert!(request.to_string().contains(&port_line));
+        }
+        assert_eq!(factsheet.api_endpoint_schema.unwrap().len(), 2);
+        assert!(workspace.api_schema_path().exists());
+        assert_eq!(
+            agent.test_report(),
+            &BackendTestReport {
+                build_failures: 1,
+                built: true,
+                endpoints_checked: 1,
+                endpoints_passed: 1,
+            }
+        );
+
+        server.stop().await;
     }
 }
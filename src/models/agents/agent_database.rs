@@ -0,0 +1,306 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    ai_functions::aifunc_database::{
+        print_data_model, print_fixed_sql_migrations, print_sql_migrations,
+    },
+    apis::{llm_client::LlmClient, llm_error::LlmError},
+    helpers::{
+        code_blocks::extract_tagged_code,
+        command_line::PrintCommand,
+        generals::{
+            add_project_dependency, ai_task_request, ai_task_request_decoded, save_migration,
+        },
+        migration_check::check_migrations,
+        workspace::Workspace,
+    },
+    models::agent_basic::basic_agent::{AgentState, BasicAgent},
+};
+
+use super::agent_traits::{DataModel, Entity, FactSheet, Migration, SpecialFunctions};
+
+const MIGRATION_NAME: &str = "0001_initial_schema.sql";
+
+// Installed in the project for the backend to apply the migrations with
+const RUSQLITE_VERSION: &str = "0.32";
+
+#[derive(Debug)]
+pub struct AgentDatabaseArchitect {
+    attributes: BasicAgent,
+    llm: Arc<dyn LlmClient>,
+    workspace: Workspace,
+    entities: Vec<Entity>,
+    migration_sql: Option<String>,
+    migration_errors: Option<String>,
+    fix_count: u8,
+}
+
+impl AgentDatabaseArchitect {
+    pub fn new(llm: Arc<dyn LlmClient>, workspace: Workspace) -> Self {
+        let attributes = BasicAgent {
+            objective: "Designs the data model and SQLite migrations for the web server"
+                .to_string(),
+            position: "Database Architect".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            llm,
+            workspace,
+            entities: vec![],
+            migration_sql: None,
+            migration_errors: None,
+            fix_count: 0,
+        }
+    }
+
+    async fn call_data_model(&mut self, factsheet: &FactSheet) -> Result<(), LlmError> {
+        self.entities = ai_task_request_decoded::<Vec<Entity>>(
+            self.llm.as_ref(),
+            factsheet.project_description.clone(),
+            &mut self.attributes,
+            get_function_string!(print_data_model),
+            print_data_model,
+            false,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn call_sql_migrations(&mut self) -> Result<(), LlmError> {
+        let msg_context = format!("DATA_MODEL: {}", self.entities_json());
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_sql_migrations),
+            print_sql_migrations,
+            false,
+        )
+        .await?;
+
+        self.migration_sql = Some(extract_sql(&ai_response));
+
+        Ok(())
+    }
+
+    async fn call_fixed_sql_migrations(&mut self) -> Result<(), LlmError> {
+        // Concat instructions
+        let msg_context = format!(
+            "DATA_MODEL: {} \n BROKEN_MIGRATION: {:?} \n ERRORS: {:?} \n",
+            self.entities_json(),
+            self.migration_sql,
+            self.migration_errors
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_sql_migrations),
+            print_fixed_sql_migrations,
+            false,
+        )
+        .await?;
+
+        self.migration_sql = Some(extract_sql(&ai_response));
+
+        Ok(())
+    }
+
+    fn entities_json(&self) -> String {
+        serde_json::to_string(&self.entities).expect("Entities are always serializable")
+    }
+}
+
+// Migrations are asked for without fences, but a fenced reply is unwrapped too
+fn extract_sql(ai_response: &str) -> String {
+    extract_tagged_code(ai_response, &["sql", ""]).unwrap_or_else(|| ai_response.to_string())
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDatabaseArchitect {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    async fn execute(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let is_crud_required = factsheet
+            .project_scope
+            .is_some_and(|scope| scope.is_crud_required);
+
+        if !is_crud_required {
+            PrintCommand::AICall.print_agent_message(
+                self.attributes.position.as_str(),
+                "No CRUD required, skipping the database design",
+            );
+            self.attributes.state = AgentState::Finished;
+        }
+
+        while self.attributes.state != AgentState::Finished {
+            match &self.attributes.state {
+                AgentState::Discovery => {
+                    self.call_data_model(factsheet).await?;
+                    self.attributes.state = AgentState::Working;
+                }
+                AgentState::Working => {
+                    if self.migration_errors.is_none() {
+                        self.call_sql_migrations().await?;
+                    } else {
+                        self.call_fixed_sql_migrations().await?;
+                    }
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+                AgentState::UnitTesting => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Database Unit Testing: applying migrations to an in-memory SQLite database...",
+                    );
+
+                    let data_model = DataModel {
+                        entities: self.entities.clone(),
+                        migrations: vec![Migration {
+                            name: MIGRATION_NAME.to_string(),
+                            sql: self.migration_sql.clone().unwrap_or_default(),
+                        }],
+                    };
+
+                    let errors = check_migrations(&data_model)?;
+                    if errors.is_empty() {
+                        for migration in &data_model.migrations {
+                            save_migration(&self.workspace, migration);
+                        }
+                        add_project_dependency(
+                            &self.workspace,
+                            "rusqlite",
+                            RUSQLITE_VERSION,
+                            &["bundled"],
+                        );
+                        factsheet.data_model = Some(data_model);
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    self.fix_count += 1;
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!("Database Unit Testing: {}", errors.join(", ")),
+                    );
+
+                    // Exit if too many bugs
+                    if self.fix_count > 2 {
+                        return Err(format!(
+                            "Migrations still fail against SQLite: {}",
+                            errors.join(", ")
+                        )
+                        .into());
+                    }
+
+                    // Pass back for rework
+                    self.migration_errors = Some(errors.join("\n"));
+                    self.attributes.state = AgentState::Working;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apis::{
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+        },
+        helpers::test_support::scratch_workspace,
+        models::agents::agent_traits::ProjectScope,
+    };
+    use std::fs;
+
+    #[tokio::test]
+    async fn tests_designing_database() {
+        let workspace = scratch_workspace("database_architect");
+
+        // The first migration leaves out the user table and references users instead
+        let entities = r#"[
+            {"name": "user", "fields": [
+                {"name": "id", "sql_type": "INTEGER", "is_primary_key": true, "is_nullable": false, "references": null}
+            ]},
+            {"name": "task", "fields": [
+                {"name": "id", "sql_type": "INTEGER", "is_primary_key": true, "is_nullable": false, "references": null},
+                {"name": "user_id", "sql_type": "INTEGER", "is_primary_key": false, "is_nullable": false, "references": "user.id"}
+            ]}
+        ]"#;
+        let fixed_sql = "CREATE TABLE user (id INTEGER PRIMARY KEY);\nCREATE TABLE task (id INTEGER PRIMARY KEY, user_id INTEGER NOT NULL REFERENCES user (id));\n";
+        let script = MockScript::new()
+            .reply("print_data_model", entities)
+            .reply(
+                "print_sql_migrations",
+                "CREATE TABLE task (id INTEGER PRIMARY KEY, user_id INTEGER NOT NULL REFERENCES users (id));",
+            )
+            .reply(
+                "print_fixed_sql_migrations",
+                &format!("```sql\n{}```", fixed_sql),
+            );
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = AgentDatabaseArchitect::new(llm, workspace.clone());
+
+        let mut factsheet = FactSheet {
+            project_description: "build a todo list backend for several users".to_string(),
+            project_scope: Some(ProjectScope {
+                is_crud_required: true,
+                is_user_login_and_logout: false,
+                is_external_urls_required: false,
+            }),
+            ..Default::default()
+        };
+
+        agent
+            .execute(&mut factsheet)
+            .await
+            .expect("Failed to execute database architect agent");
+
+        assert_eq!(
+            server.calls(),
+            vec![
+                "print_data_model",
+                "print_sql_migrations",
+                "print_fixed_sql_migrations"
+            ]
+        );
+        let fix_request = server.requests()[2].to_string();
+        assert!(fix_request.contains("No table for entity user"));
+        assert!(fix_request.contains("Table task references users, which does not exist"));
+
+        let data_model = factsheet.data_model.unwrap();
+        assert_eq!(
+            data_model.entities[1].fields[1].references.as_deref(),
+            Some("user.id")
+        );
+        assert_eq!(data_model.migrations[0].sql, fixed_sql);
+        assert_eq!(
+            fs::read_to_string(workspace.migrations_dir().join(MIGRATION_NAME)).unwrap(),
+            fixed_sql
+        );
+        assert!(fs::read_to_string(workspace.cargo_toml_path())
+            .unwrap()
+            .contains(r#"rusqlite = { version = "0.32", features = ["bundled"] }"#));
+
+        server.stop().await;
+    }
+}
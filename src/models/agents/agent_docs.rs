@@ -0,0 +1,409 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    ai_functions::aifunc_docs::{print_fixed_readme, print_readme},
+    apis::{llm_client::LlmClient, llm_error::LlmError},
+    helpers::{
+        command_line::PrintCommand,
+        doc_checks::{
+            check_curl_calls, check_env_vars, find_env_vars, find_registered_routes, Endpoint,
+        },
+        fetch_calls::route_matches,
+        generals::{ai_task_request, save_api_reference, save_readme},
+        workspace::Workspace,
+    },
+    models::agent_basic::basic_agent::{AgentState, BasicAgent},
+};
+
+use super::agent_traits::{routes_json, FactSheet, RouteObject, SpecialFunctions};
+
+// Filled in for {id} style segments in curl examples
+const EXAMPLE_SEGMENT: &str = "1";
+
+#[derive(Debug)]
+pub struct AgentTechnicalWriter {
+    attributes: BasicAgent,
+    llm: Arc<dyn LlmClient>,
+    workspace: Workspace,
+    readme: Option<String>,
+    readme_errors: Option<String>,
+    fix_count: u8,
+}
+
+impl AgentTechnicalWriter {
+    pub fn new(llm: Arc<dyn LlmClient>, workspace: Workspace) -> Self {
+        let attributes = BasicAgent {
+            objective: "Documents how to run the web server and call its api endpoints".to_string(),
+            position: "Technical Writer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            llm,
+            workspace,
+            readme: None,
+            readme_errors: None,
+            fix_count: 0,
+        }
+    }
+
+    async fn call_readme(
+        &mut self,
+        factsheet: &FactSheet,
+        routes: &[RouteObject],
+        env_vars: &[String],
+    ) -> Result<(), LlmError> {
+        // Concat instructions
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {} \n API_BASE_URL: {} \n ENV_VARS: {:?} \n API_ENDPOINTS: {} \n PROJECT_FILES: {:?} \n",
+            factsheet.project_description,
+            self.workspace.server_url(""),
+            env_vars,
+            routes_json(routes),
+            self.project_files()
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_readme),
+            print_readme,
+            false,
+        )
+        .await?;
+
+        self.readme = Some(strip_markdown_fence(&ai_response));
+
+        Ok(())
+    }
+
+    async fn call_fixed_readme(
+        &mut self,
+        routes: &[RouteObject],
+        env_vars: &[String],
+    ) -> Result<(), LlmError> {
+        // Concat instructions
+        let msg_context = format!(
+            "API_ENDPOINTS: {} \n ENV_VARS: {:?} \n README: {:?} \n ERRORS: {:?} \n",
+            routes_json(routes),
+            env_vars,
+            self.readme,
+            self.readme_errors
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_readme),
+            print_fixed_readme,
+            false,
+        )
+        .await?;
+
+        self.readme = Some(strip_markdown_fence(&ai_response));
+
+        Ok(())
+    }
+
+    // What earlier agents left in the workspace, so the README can mention it
+    fn project_files(&self) -> Vec<&'static str> {
+        [
+            (self.workspace.migrations_dir(), "migrations/"),
+            (
+                self.workspace.integration_tests_path(),
+                "tests/api_tests.rs",
+            ),
+            (self.workspace.frontend_dir(), "frontend/"),
+        ]
+        .into_iter()
+        .filter(|(path, _)| path.exists())
+        .map(|(_, name)| name)
+        .collect()
+    }
+
+    // Routes from the schema the code really registers. The rest are reported and left out.
+    fn documented_routes(
+        &self,
+        schema: &[RouteObject],
+        registered: Option<&[Endpoint]>,
+    ) -> Vec<RouteObject> {
+        // Nothing to compare against, e.g. a framework other than actix-web
+        let Some(registered) = registered else {
+            PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                "Could not read the routes the backend code registers, curl examples are not checked",
+            );
+            return schema.to_vec();
+        };
+
+        schema
+            .iter()
+            .filter(|route| {
+                let is_registered = registered.iter().any(|endpoint| {
+                    route.method.eq_ignore_ascii_case(&endpoint.method)
+                        && route_matches(&endpoint.path, &route.route)
+                });
+
+                if !is_registered {
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!(
+                            "{} {} is not registered in the backend code, leaving it out of the docs",
+                            route.method.to_uppercase(),
+                            route.route
+                        ),
+                    );
+                }
+                is_registered
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn check_readme(&self, registered: Option<&[Endpoint]>, env_vars: &[String]) -> Vec<String> {
+        let readme = self.readme.as_deref().unwrap_or_default();
+
+        let mut errors = registered.map_or(vec![], |routes| check_curl_calls(readme, routes));
+        errors.extend(check_env_vars(readme, env_vars));
+
+        errors
+    }
+}
+
+// The README is asked for without a fence, but a reply wrapped in one is unwrapped.
+// Only the outer fence, since the README has code blocks of its own.
+fn strip_markdown_fence(ai_response: &str) -> String {
+    let trimmed = ai_response.trim();
+
+    let inner = ["```markdown", "```md"]
+        .iter()
+        .find_map(|fence| trimmed.strip_prefix(fence))
+        .and_then(|rest| rest.strip_suffix("```"));
+
+    format!("{}\n", inner.unwrap_or(trimmed).trim())
+}
+
+// Endpoint reference with a curl example per route
+fn api_reference(routes: &[RouteObject], base_url: &str) -> String {
+    let mut reference = format!(
+        "# API reference\n\nAll endpoints are served from {}.\n",
+        base_url
+    );
+
+    for route in routes {
+        let method = route.method.to_uppercase();
+        let example_path: Vec<&str> = route
+            .route
+            .split('/')
+            .map(
+                |segment| match segment.starts_with('{') && segment.ends_with('}') {
+                    true => EXAMPLE_SEGMENT,
+                    false => segment,
+                },
+            )
+            .collect();
+
+        let mut curl = format!("curl -X {} {}{}", method, base_url, example_path.join("/"));
+        if !route.request_body.is_null() {
+            curl.push_str(&format!(
+                " \\\n  -H \"Content-Type: application/json\" \\\n  -d '{}'",
+                route.request_body
+            ));
+        }
+
+        reference.push_str(&format!(
+            "\n## {} {}\n\n```sh\n{}\n```\n",
+            method, route.route, curl
+        ));
+    }
+
+    reference
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentTechnicalWriter {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    async fn execute(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let schema = factsheet
+            .api_endpoint_schema
+            .clone()
+            .ok_or("No API endpoint schema to document")?;
+        let backend_code = factsheet
+            .backend_code
+            .clone()
+            .ok_or("No backend code to document")?;
+
+        // Docs are checked against the code, not the schema extracted from it
+        let registered = find_registered_routes(&backend_code).filter(|routes| !routes.is_empty());
+        let env_vars = find_env_vars(&backend_code);
+        let routes = self.documented_routes(&schema, registered.as_deref());
+
+        while self.attributes.state != AgentState::Finished {
+            match &self.attributes.state {
+                AgentState::Discovery => {
+                    save_api_reference(
+                        &self.workspace,
+                        &api_reference(&routes, &self.workspace.server_url("")),
+                    );
+                    self.call_readme(factsheet, &routes, &env_vars).await?;
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+                AgentState::Working => {
+                    self.call_fixed_readme(&routes, &env_vars).await?;
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+                AgentState::UnitTesting => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Docs Unit Testing: checking curl examples against the registered routes...",
+                    );
+
+                    let errors = self.check_readme(registered.as_deref(), &env_vars);
+                    if errors.is_empty() {
+                        save_readme(&self.workspace, self.readme.as_deref().unwrap_or_default());
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    self.fix_count += 1;
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!("Docs Unit Testing: {}", errors.join(", ")),
+                    );
+
+                    // Exit if too many bugs
+                    if self.fix_count > 2 {
+                        return Err(format!(
+                            "README still does not match the backend code: {}",
+                            errors.join(", ")
+                        )
+                        .into());
+                    }
+
+                    // Pass back for rework
+                    self.readme_errors = Some(errors.join("\n"));
+                    self.attributes.state = AgentState::Working;
+                }
+                _ => {}
+            }
+        }
+
+        PrintCommand::AICall.print_agent_message(
+            self.attributes.position.as_str(),
+            &format!(
+                "Docs written to {} and {}",
+                self.workspace.readme_path().display(),
+                self.workspace.api_reference_path().display()
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apis::{
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+        },
+        helpers::test_support::scratch_workspace,
+    };
+    use std::fs;
+
+    #[tokio::test]
+    async fn tests_writing_docs() {
+        let workspace = scratch_workspace("technical_writer");
+
+        let backend_code = r#"
+        async fn main() -> std::io::Result<()> {
+            let port = env::var("PORT").unwrap_or("8080".to_string());
+            HttpServer::new(move || {
+                App::new()
+                    .route("/workouts", web::post().to(create_workout))
+                    .route("/workouts/{id}", web::get().to(read_workout))
+            })
+        }
+        "#;
+
+        // The first README calls a stats route the code does not have and leaves out PORT
+        let script = MockScript::new()
+            .reply(
+                "print_readme",
+                "```markdown\n# Workouts\n\n```sh\ncurl http://localhost:8080/stats\n```\n```",
+            )
+            .reply(
+                "print_fixed_readme",
+                "# Workouts\n\nSet PORT to change the port.\n\n```sh\ncurl http://localhost:8080/workouts/1\n```\n",
+            );
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = AgentTechnicalWriter::new(llm, workspace.clone());
+
+        let route = |method: &str, route: &str, request_body: serde_json::Value| RouteObject {
+            is_route_dyanmic: route.contains('{').to_string(),
+            method: method.to_string(),
+            request_body,
+            route: route.to_string(),
+        };
+        let mut factsheet = FactSheet {
+            project_description: "build a web server for tracking my workouts".to_string(),
+            backend_code: Some(backend_code.to_string()),
+            api_endpoint_schema: Some(vec![
+                route(
+                    "post",
+                    "/workouts",
+                    serde_json::json!({"name": "run", "minutes": 30}),
+                ),
+                route("get", "/workouts/{id}", serde_json::Value::Null),
+                route("delete", "/workouts/{id}", serde_json::Value::Null),
+            ]),
+            ..Default::default()
+        };
+
+        agent
+            .execute(&mut factsheet)
+            .await
+            .expect("Failed to execute technical writer agent");
+
+        assert_eq!(server.calls(), vec!["print_readme", "print_fixed_readme"]);
+        let fix_request = server.requests()[1].to_string();
+        assert!(fix_request
+            .contains("curl example GET /stats calls a route the code does not register"));
+        assert!(fix_request.contains("Environment variable PORT is not documented"));
+
+        let readme = fs::read_to_string(workspace.readme_path()).unwrap();
+        assert!(readme.contains("curl http://localhost:8080/workouts/1"));
+
+        // The delete route is in the schema but not in the code
+        let reference = fs::read_to_string(workspace.api_reference_path()).unwrap();
+        assert!(reference.contains(
+            &format!(
+                "curl -X POST {} \\\n  -H \"Content-Type: application/json\" \\\n  -d '{{\"minutes\":30,\"name\":\"run\"}}'",
+                workspace.server_url("/workouts")
+            )
+        ));
+        assert!(reference.contains(&format!(
+            "curl -X GET {}",
+            workspace.server_url("/workouts/1")
+        )));
+        assert!(!reference.contains("DELETE"));
+
+        server.stop().await;
+    }
+}
@@ -0,0 +1,341 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    ai_functions::aifunc_frontend::{print_fixed_frontend_code, print_frontend_code},
+    apis::{llm_client::LlmClient, llm_error::LlmError},
+    helpers::{
+        code_blocks::extract_tagged_code,
+        command_line::PrintCommand,
+        fetch_calls::{find_fetch_calls, route_matches},
+        generals::{ai_task_request, save_frontend_file},
+        workspace::Workspace,
+    },
+    models::agent_basic::basic_agent::{AgentState, BasicAgent},
+};
+
+use super::agent_traits::{routes_json, FactSheet, RouteObject, SpecialFunctions};
+
+// Files the frontend is made of, and the code block tags each is taken from
+const FRONTEND_FILES: [(&str, &[&str]); 3] = [
+    ("index.html", &["html"]),
+    ("app.js", &["javascript", "js"]),
+    ("styles.css", &["css"]),
+];
+
+#[derive(Debug)]
+pub struct AgentFrontendDeveloper {
+    attributes: BasicAgent,
+    llm: Arc<dyn LlmClient>,
+    workspace: Workspace,
+    // File name and contents of the latest reply
+    files: Vec<(&'static str, String)>,
+    endpoint_errors: Option<String>,
+    fix_count: u8,
+}
+
+impl AgentFrontendDeveloper {
+    pub fn new(llm: Arc<dyn LlmClient>, workspace: Workspace) -> Self {
+        let attributes = BasicAgent {
+            objective: "Develops a static frontend that calls the web server's api endpoints"
+                .to_string(),
+            position: "Frontend Developer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            llm,
+            workspace,
+            files: vec![],
+            endpoint_errors: None,
+            fix_count: 0,
+        }
+    }
+
+    async fn call_initial_frontend_code(
+        &mut self,
+        factsheet: &FactSheet,
+        routes: &[RouteObject],
+    ) -> Result<(), LlmError> {
+        // Concat instructions
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {} \n API_BASE_URL: {} \n API_ENDPOINTS: {} \n",
+            factsheet.project_description,
+            self.workspace.server_url(""),
+            routes_json(routes)
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_frontend_code),
+            print_frontend_code,
+            false,
+        )
+        .await?;
+
+        self.save_generated_code(&ai_response);
+
+        Ok(())
+    }
+
+    async fn call_fixed_frontend_code(&mut self, routes: &[RouteObject]) -> Result<(), LlmError> {
+        let frontend: String = self
+            .files
+            .iter()
+            .map(|(file_name, code)| format!("{}:\n{}\n", file_name, code))
+            .collect();
+
+        // Concat instructions
+        let msg_context = format!(
+            "API_ENDPOINTS: {} \n FRONTEND: {} \n ERRORS: {:?} \n",
+            routes_json(routes),
+            frontend,
+            self.endpoint_errors
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_frontend_code),
+            print_fixed_frontend_code,
+            false,
+        )
+        .await?;
+
+        self.save_generated_code(&ai_response);
+
+        Ok(())
+    }
+
+    fn save_generated_code(&mut self, ai_response: &str) {
+        self.files = FRONTEND_FILES
+            .iter()
+            .filter_map(|(file_name, tags)| {
+                extract_tagged_code(ai_response, tags).map(|code| (*file_name, code))
+            })
+            .collect();
+
+        for (file_name, code) in &self.files {
+            save_frontend_file(&self.workspace, file_name, code);
+        }
+    }
+
+    // Files missing from the reply and fetch calls to endpoints the schema does not have.
+    // Routes the frontend leaves out are only reported.
+    fn check_frontend(&self, routes: &[RouteObject]) -> Vec<String> {
+        let mut errors: Vec<String> = FRONTEND_FILES
+            .iter()
+            .filter(|(file_name, _)| !self.files.iter().any(|(name, _)| name == file_name))
+            .map(|(file_name, _)| format!("No code block for {}", file_name))
+            .collect();
+
+        let calls: Vec<_> = self
+            .files
+            .iter()
+            .flat_map(|(_, code)| find_fetch_calls(code))
+            .collect();
+
+        for call in &calls {
+            let Some(path) = &call.path else {
+                PrintCommand::Issue.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "Could not tell which endpoint a fetch call requests",
+                );
+                continue;
+            };
+
+            let is_known = routes.iter().any(|route| {
+                route.method.eq_ignore_ascii_case(&call.method) && route_matches(&route.route, path)
+            });
+
+            let error = format!(
+                "{} {} is not in API_ENDPOINTS",
+                call.method.to_uppercase(),
+                path
+            );
+            if !is_known && !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+
+        for route in routes {
+            let is_called = calls.iter().any(|call| {
+                call.path.as_ref().is_some_and(|path| {
+                    route.method.eq_ignore_ascii_case(&call.method)
+                        && route_matches(&route.route, path)
+                })
+            });
+
+            if !is_called {
+                PrintCommand::Issue.print_agent_message(
+                    self.attributes.position.as_str(),
+                    &format!(
+                        "{} {} is not called by the frontend",
+                        route.method.to_uppercase(),
+                        route.route
+                    ),
+                );
+            }
+        }
+
+        errors
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentFrontendDeveloper {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    async fn execute(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let routes = factsheet
+            .api_endpoint_schema
+            .clone()
+            .ok_or("No API endpoint schema to build the frontend for")?;
+
+        while self.attributes.state != AgentState::Finished {
+            match &self.attributes.state {
+                AgentState::Discovery => {
+                    self.call_initial_frontend_code(factsheet, &routes).await?;
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+                AgentState::Working => {
+                    self.call_fixed_frontend_code(&routes).await?;
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+                AgentState::UnitTesting => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Frontend Code Unit Testing: checking endpoints against the API schema...",
+                    );
+
+                    let errors = self.check_frontend(&routes);
+                    if errors.is_empty() {
+                        self.endpoint_errors = None;
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    self.fix_count += 1;
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!("Frontend Code Unit Testing: {}", errors.join(", ")),
+                    );
+
+                    // Exit if too many bugs
+                    if self.fix_count > 2 {
+                        return Err(format!(
+                            "Frontend still calls endpoints missing from the API schema: {}",
+                            errors.join(", ")
+                        )
+                        .into());
+                    }
+
+                    // Pass back for rework
+                    self.endpoint_errors = Some(errors.join("\n"));
+                    self.attributes.state = AgentState::Working;
+                }
+                _ => {}
+            }
+        }
+
+        PrintCommand::AICall.print_agent_message(
+            self.attributes.position.as_str(),
+            &format!(
+                "Frontend written to {}",
+                self.workspace.frontend_dir().display()
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apis::{
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+        },
+        helpers::test_support::scratch_workspace,
+    };
+    use std::fs;
+
+    fn frontend_reply(script: &str) -> String {
+        format!(
+            "```html\n<link rel=\"stylesheet\" href=\"styles.css\">\n<script src=\"app.js\"></script>\n```\n```javascript\nconst API_BASE = \"http://localhost:8080\";\n{}\n```\n```css\nbody {{ margin: 0; }}\n```\n",
+            script
+        )
+    }
+
+    #[tokio::test]
+    async fn tests_writing_frontend_code() {
+        let workspace = scratch_workspace("frontend_developer");
+
+        // The first frontend calls a stats endpoint the backend does not have
+        let script = MockScript::new()
+            .reply(
+                "print_frontend_code",
+                &frontend_reply(
+                    "fetch(`${API_BASE}/workouts`);\nfetch(API_BASE + \"/stats\", { method: \"POST\" });",
+                ),
+            )
+            .reply(
+                "print_fixed_frontend_code",
+                &frontend_reply(
+                    "fetch(`${API_BASE}/workouts`);\nfetch(`${API_BASE}/workouts/${id}`, { method: \"DELETE\" });",
+                ),
+            );
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = AgentFrontendDeveloper::new(llm, workspace.clone());
+
+        let route = |method: &str, route: &str| RouteObject {
+            is_route_dyanmic: route.contains('{').to_string(),
+            method: method.to_string(),
+            request_body: serde_json::Value::Null,
+            route: route.to_string(),
+        };
+        let mut factsheet = FactSheet {
+            project_description: "build a website for tracking my workouts".to_string(),
+            api_endpoint_schema: Some(vec![
+                route("get", "/workouts"),
+                route("delete", "/workouts/{id}"),
+            ]),
+            ..Default::default()
+        };
+
+        agent
+            .execute(&mut factsheet)
+            .await
+            .expect("Failed to execute frontend developer agent");
+
+        assert_eq!(
+            server.calls(),
+            vec!["print_frontend_code", "print_fixed_frontend_code"]
+        );
+        let fix_request = server.requests()[1].to_string();
+        assert!(fix_request.contains("POST /stats is not in API_ENDPOINTS"));
+
+        let app_js = fs::read_to_string(workspace.frontend_dir().join("app.js")).unwrap();
+        assert!(app_js.contains("fetch(`${API_BASE}/workouts/${id}`, { method: \"DELETE\" });"));
+        for (file_name, _) in FRONTEND_FILES {
+            assert!(workspace.frontend_dir().join(file_name).exists());
+        }
+
+        server.stop().await;
+    }
+}
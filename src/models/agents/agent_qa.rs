@@ -0,0 +1,344 @@
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time;
+
+use crate::{
+    ai_functions::aifunc_qa::{print_fixed_integration_tests, print_integration_tests},
+    apis::{llm_client::LlmClient, llm_error::LlmError},
+    helpers::{
+        code_blocks::extract_rust_code,
+        command_line::{confirm_safe_code, PrintCommand},
+        generals::{ai_task_request, save_integration_tests},
+        test_results::parse_test_output,
+        workspace::Workspace,
+    },
+    models::agent_basic::basic_agent::{AgentState, BasicAgent},
+};
+
+use super::agent_traits::{FactSheet, SpecialFunctions};
+
+#[derive(Debug)]
+pub struct AgentQaEngineer {
+    attributes: BasicAgent,
+    llm: Arc<dyn LlmClient>,
+    workspace: Workspace,
+    test_code: Option<String>,
+    test_errors: Option<String>,
+    test_fix_count: u8,
+}
+
+impl AgentQaEngineer {
+    pub fn new(llm: Arc<dyn LlmClient>, workspace: Workspace) -> Self {
+        let attributes = BasicAgent {
+            objective: "Writes and runs integration tests against the web server".to_string(),
+            position: "QA Engineer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            llm,
+            workspace,
+            test_code: None,
+            test_errors: None,
+            test_fix_count: 0,
+        }
+    }
+
+    async fn call_integration_tests(&mut self, factsheet: &FactSheet) -> Result<(), LlmError> {
+        // Concat instructions
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {} \n API_BASE_URL: {} \n API_ENDPOINTS: {} \n",
+            factsheet.project_description,
+            self.workspace.server_url(""),
+            serde_json::to_string(&factsheet.api_endpoint_schema)
+                .expect("Route objects are always serializable")
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_integration_tests),
+            print_integration_tests,
+            false,
+        )
+        .await?;
+
+        self.save_generated_tests(&ai_response);
+
+        Ok(())
+    }
+
+    async fn call_fixed_integration_tests(
+        &mut self,
+        factsheet: &FactSheet,
+    ) -> Result<(), LlmError> {
+        // Concat instructions
+        let msg_context = format!(
+            "API_ENDPOINTS: {:?} \n BROKEN_TESTS: {:?} \n ERRORS: {:?} \n
+            THIS FUNCTION ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+            factsheet.api_endpoint_schema, self.test_code, self.test_errors
+        );
+
+        let ai_response = ai_task_request(
+            self.llm.as_ref(),
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_integration_tests),
+            print_fixed_integration_tests,
+            false,
+        )
+        .await?;
+
+        self.save_generated_tests(&ai_response);
+
+        Ok(())
+    }
+
+    fn save_generated_tests(&mut self, ai_response: &str) {
+        let extracted = extract_rust_code(ai_response);
+
+        if let Some(warning) = &extracted.warning {
+            PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), warning);
+        }
+
+        save_integration_tests(&self.workspace, &extracted.code);
+        self.test_code = Some(extracted.code);
+    }
+
+    // Run the suite against a freshly started server
+    async fn run_integration_tests(&self) -> Result<Output, std::io::Error> {
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Integration Testing: Starting web server...",
+        );
+
+        let mut run_backend_server = Command::new("cargo")
+            .arg("run")
+            .current_dir(&self.workspace.root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Integration Testing: Running tests on server in 5 seconds...",
+        );
+        time::sleep(Duration::from_secs(5)).await;
+
+        let test_run = Command::new("cargo")
+            .args(["test", "--test", "api_tests"])
+            .current_dir(&self.workspace.root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output();
+
+        run_backend_server.kill()?;
+        // Reap the killed process so it doesn't linger as a zombie
+        let _ = run_backend_server.wait();
+
+        test_run
+    }
+}
+
+// What the backend is asked to fix, None when every test passed
+pub fn describe_failures(factsheet: &FactSheet) -> Option<String> {
+    let failures: String = factsheet
+        .integration_tests
+        .iter()
+        .flatten()
+        .filter(|test| !test.passed)
+        .map(|test| {
+            format!(
+                "Integration test {} failed:\n{}\n",
+                test.name,
+                test.failure.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+
+    Some(failures).filter(|failures| !failures.is_empty())
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentQaEngineer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    async fn execute(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if factsheet.api_endpoint_schema.is_none() {
+            return Err("No API endpoint schema to write integration tests for".into());
+        }
+
+        // Running again retests the fixed backend with the same suite
+        if self.attributes.state == AgentState::Finished {
+            self.attributes.state = AgentState::UnitTesting;
+        }
+
+        while self.attributes.state != AgentState::Finished {
+            match &self.attributes.state {
+                AgentState::Discovery => {
+                    self.call_integration_tests(factsheet).await?;
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+                AgentState::UnitTesting => {
+                    // ! Guard :: ENSURE AI SAFETY
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Integration Testing: Requesting user input",
+                    );
+
+                    let is_safe_code = !self.workspace.confirm_before_run || confirm_safe_code();
+                    if !is_safe_code {
+                        return Err("Running the integration tests was not approved".into());
+                    }
+
+                    let test_run = self.run_integration_tests().await?;
+                    let tests = parse_test_output(&String::from_utf8_lossy(&test_run.stdout));
+
+                    // Nothing ran, so the suite itself is broken
+                    if tests.is_empty() {
+                        self.test_fix_count += 1;
+                        self.test_errors = Some(String::from_utf8_lossy(&test_run.stderr).into());
+
+                        if self.test_fix_count > 2 {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Integration Testing: Too many bugs found in tests",
+                            );
+
+                            return Err("Too many bugs found in integration tests".into());
+                        }
+
+                        self.call_fixed_integration_tests(factsheet).await?;
+                        continue;
+                    }
+
+                    let failed = tests.iter().filter(|test| !test.passed).count();
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!(
+                            "Integration Testing: {} passed, {} failed",
+                            tests.len() - failed,
+                            failed
+                        ),
+                    );
+                    // Failures stay on the factsheet for the manager to send to the backend
+                    factsheet.integration_tests = Some(tests);
+                    self.attributes.state = AgentState::Finished;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::{IntegrationTest, RouteObject};
+    use crate::{
+        apis::{
+            llm_client::create_llm_client,
+            mock_llm::{MockLlmServer, MockScript},
+        },
+        helpers::test_support::{scratch_workspace, scripted_server_code},
+    };
+    use std::fs;
+
+    // Std-only tests, the scratch project has no dependencies
+    fn scripted_tests(port: u16) -> String {
+        format!(
+            r#"use std::io::{{Read, Write}};
+use std::net::TcpStream;
+
+fn get(path: &str) -> String {{
+    let mut stream = TcpStream::connect("127.0.0.1:{port}").unwrap();
+    let request = format!("GET {{}} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}}
+
+#[test]
+fn tests_listing_workouts() {{
+    let response = get("/workouts");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("ok"), "unexpected body in {{}}", response);
+}}
+"#
+        )
+    }
+
+    #[tokio::test]
+    async fn tests_running_integration_tests() {
+        let workspace = scratch_workspace("qa_engineer");
+
+        // The server answers [] until it is fixed to answer ok
+        let broken_code = scripted_server_code(workspace.port, 1);
+        fs::write(workspace.main_path(), &broken_code).unwrap();
+
+        let script =
+            MockScript::new().reply("print_integration_tests", &scripted_tests(workspace.port));
+
+        let server = MockLlmServer::start(script).await;
+        let llm = create_llm_client(server.config()).unwrap();
+        let mut agent = AgentQaEngineer::new(llm, workspace.clone());
+
+        let mut factsheet = FactSheet {
+            project_description: "build a web server for tracking my workouts".to_string(),
+            backend_code: Some(broken_code),
+            api_endpoint_schema: Some(vec![RouteObject {
+                is_route_dyanmic: "false".to_string(),
+                method: "get".to_string(),
+                request_body: serde_json::Value::Null,
+                route: "/workouts".to_string(),
+            }]),
+            ..Default::default()
+        };
+
+        agent
+            .execute(&mut factsheet)
+            .await
+            .expect("Failed to execute QA engineer agent");
+
+        let failures = describe_failures(&factsheet).unwrap();
+        assert!(failures.starts_with("Integration test tests_listing_workouts failed"));
+        assert!(failures.contains("unexpected body"));
+
+        // Running again retests with the same suite
+        let fixed_code = scripted_server_code(workspace.port, 1).replace("[]", "ok");
+        fs::write(workspace.main_path(), fixed_code).unwrap();
+
+        agent
+            .execute(&mut factsheet)
+            .await
+            .expect("Failed to retest with QA engineer agent");
+
+        assert_eq!(server.calls(), vec!["print_integration_tests"]);
+        assert_eq!(
+            factsheet.integration_tests,
+            Some(vec![IntegrationTest {
+                name: "tests_listing_workouts".to_string(),
+                passed: true,
+                failure: None,
+            }])
+        );
+        assert_eq!(describe_failures(&factsheet), None);
+        assert!(workspace.integration_tests_path().exists());
+
+        server.stop().await;
+    }
+}
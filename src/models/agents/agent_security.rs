@@ -0,0 +1,102 @@
+use crate::{
+    helpers::{command_line::PrintCommand, security_checks::scan_backend_code},
+    models::agent_basic::basic_agent::{AgentState, BasicAgent},
+};
+
+use super::agent_traits::{FactSheet, SecurityFinding, Severity};
+
+// Static checks only, so there is no LLM to call. Run by the backend developer
+// before its code is built, rather than as an agent of its own
+#[derive(Debug)]
+pub struct AgentSecurityReviewer {
+    attributes: BasicAgent,
+}
+
+impl AgentSecurityReviewer {
+    pub fn new() -> Self {
+        let attributes = BasicAgent {
+            objective: "Reviews the backend code for security issues before it is run".to_string(),
+            position: "Security Reviewer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self { attributes }
+    }
+
+    // Records every finding on the factsheet and returns the ones that block running the code
+    pub fn review(&mut self, factsheet: &mut FactSheet) -> Vec<SecurityFinding> {
+        let findings = scan_backend_code(factsheet.backend_code.as_deref().unwrap_or_default());
+
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            &format!("Security Review: {} findings", findings.len()),
+        );
+        for finding in &findings {
+            let command = match finding.severity {
+                Severity::High => PrintCommand::Issue,
+                _ => PrintCommand::UnitTest,
+            };
+            command.print_agent_message(self.attributes.position.as_str(), &finding.to_string());
+        }
+
+        let blocking = findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::High)
+            .cloned()
+            .collect();
+        factsheet.security_findings = Some(findings);
+
+        blocking
+    }
+}
+
+impl Default for AgentSecurityReviewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_reviewing_backend_code() {
+        let mut factsheet = FactSheet {
+            project_description: "build a web server for tracking my workouts".to_string(),
+            backend_code: Some(
+                "let cors = Cors::default().allow_any_header();\nlet db = fs::read_to_string(\"db.json\");"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+
+        let mut agent = AgentSecurityReviewer::new();
+        let blocking = agent.review(&mut factsheet);
+        assert!(
+            blocking.is_empty(),
+            "Low severity findings should not block the run"
+        );
+        assert_eq!(factsheet.security_findings.as_ref().unwrap().len(), 1);
+
+        // The high ones sort ahead of the CORS finding on the first line
+        factsheet.backend_code = Some(
+            "let cors = Cors::permissive();\nunsafe { run() }\nlet out = Command::new(\"sh\");"
+                .to_string(),
+        );
+        let blocking = agent.review(&mut factsheet);
+
+        assert_eq!(blocking.len(), 2);
+        let severities: Vec<Severity> = factsheet
+            .security_findings
+            .unwrap()
+            .iter()
+            .map(|finding| finding.severity)
+            .collect();
+        assert_eq!(
+            severities,
+            [Severity::High, Severity::High, Severity::Medium]
+        );
+    }
+}
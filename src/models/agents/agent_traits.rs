@@ -1,32 +1,122 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 
 use async_trait::async_trait;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use strum_macros::Display;
 
 use crate::models::agent_basic::basic_agent::BasicAgent;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct RouteObject {
+    // print_rest_api_endpoints asks for the correct spelling
+    #[serde(alias = "is_route_dynamic")]
     pub is_route_dyanmic: String,
     pub method: String,
     pub request_body: serde_json::Value,
     pub route: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+// Routes as they are given to the LLM in a prompt
+pub fn routes_json(routes: &[RouteObject]) -> String {
+    serde_json::to_string(routes).expect("Route objects are always serializable")
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
 pub struct ProjectScope {
     pub is_crud_required: bool,
     pub is_user_login_and_logout: bool,
     pub is_external_urls_required: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct EntityField {
+    pub name: String,
+    // INTEGER, TEXT, REAL or BLOB
+    pub sql_type: String,
+    pub is_primary_key: bool,
+    pub is_nullable: bool,
+    // "entity.field" of a foreign key
+    #[serde(default)]
+    pub references: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct Entity {
+    pub name: String,
+    pub fields: Vec<EntityField>,
+}
+
+// A SQL script saved as migrations/<name>
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Migration {
+    pub name: String,
+    pub sql: String,
+}
+
+// Tables the backend persists to, with the migrations that create them in SQLite
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DataModel {
+    pub entities: Vec<Entity>,
+    pub migrations: Vec<Migration>,
+}
+
+// One test of the generated project's integration suite
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IntegrationTest {
+    pub name: String,
+    pub passed: bool,
+    // Panic message and output of a failed test
+    pub failure: Option<String>,
+}
+
+// Ordered so the most severe sorts last
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[strum(serialize_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    // Blocks running the code
+    High,
+}
+
+// Something the security review found in the backend code
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SecurityFinding {
+    pub severity: Severity,
+    pub check: String,
+    // 1-based line in backend_code
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SecurityFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] line {}: {}: {}",
+            self.severity, self.line, self.check, self.message
+        )
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
 pub struct FactSheet {
     pub project_description: String,
     pub project_scope: Option<ProjectScope>,
     pub external_urls: Option<Vec<String>>,
     pub backend_code: Option<String>,
     pub api_endpoint_schema: Option<Vec<RouteObject>>,
+    // Only designed when CRUD is required
+    #[serde(default)]
+    pub data_model: Option<DataModel>,
+    // Results of the latest run of the QA engineer's suite
+    #[serde(default)]
+    pub integration_tests: Option<Vec<IntegrationTest>>,
+    // Most severe first, from the latest review of backend_code
+    #[serde(default)]
+    pub security_findings: Option<Vec<SecurityFinding>>,
 }
 
 #[async_trait]
@@ -39,4 +129,8 @@ pub trait SpecialFunctions: Debug {
         &mut self,
         factsheet: &mut FactSheet,
     ) -> Result<(), Box<dyn std::error::Error>>;
+
+    // Bugs a later agent found in this one's output, fixed by the next execute.
+    // Agents that can't fix anything ignore them.
+    fn report_bugs(&mut self, _bug_errors: String) {}
 }
@@ -1,21 +1,191 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Message {
     pub role: String,
     pub content: String,
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct ChatCompletion {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    pub schema: serde_json::Value,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+// Constrains the completion to plain text, any JSON object, or JSON matching a schema
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    // JSON schema of the arguments
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: FunctionDefinition,
+}
+
+impl Tool {
+    pub fn function(name: &str, description: &str, parameters: serde_json::Value) -> Self {
+        Self {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolChoiceFunction {
+    pub name: String,
+}
+
+// "none", "auto" or "required", or a specific function the model must call
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ToolChoice {
+    Mode(String),
+    Function {
+        #[serde(rename = "type")]
+        tool_type: String,
+        function: ToolChoiceFunction,
+    },
+}
+
+impl ToolChoice {
+    pub fn function(name: &str) -> Self {
+        Self::Function {
+            tool_type: "function".to_string(),
+            function: ToolChoiceFunction {
+                name: name.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    // JSON encoded arguments, as generated by the model
+    pub arguments: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCall {
+    #[serde(default)]
+    pub id: String,
+    #[serde(rename = "type", default)]
+    pub tool_type: String,
+    pub function: FunctionCall,
+}
+
+// Request level settings beyond the messages. Unset sampling fields fall back to
+// the client's config, settings a provider does not support are ignored.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ChatOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+impl ChatOptions {
+    // Response formats and tools need the whole reply at once, so they are never streamed
+    pub fn is_structured(&self) -> bool {
+        self.response_format.is_some() || self.tools.is_some()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+}
+
+// Content and token usage of a completion, whichever provider produced it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LlmResponse {
+    pub content: String,
+    pub usage: Usage,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl LlmResponse {
+    // Arguments of the first tool call, or the content when no tool was called
+    pub fn output(self) -> String {
+        match self.tool_calls.into_iter().next() {
+            Some(call) => call.function.arguments,
+            None => self.content,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct APIMessage {
-    pub content: String,
+    // Null when the model answers with tool calls only
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,4 +196,129 @@ pub struct APIChoice {
 #[derive(Debug, Deserialize)]
 pub struct APIResponse {
     pub choices: Vec<APIChoice>,
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct APIError {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub error_type: Option<String>,
+    pub code: Option<serde_json::Value>,
+}
+
+// OpenAI and Anthropic send an error object, Ollama sends a plain string
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum APIErrorField {
+    Detailed(APIError),
+    Message(String),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIErrorResponse {
+    pub error: APIErrorField,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIDelta {
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChoice {
+    pub delta: APIDelta,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChunk {
+    #[serde(default)]
+    pub choices: Vec<APIStreamChoice>,
+    // Only set on the final chunk when include_usage is requested
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AnthropicMessages {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub messages: Vec<Message>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnthropicContent {
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub struct AnthropicUsage {
+    #[serde(default)]
+    pub input_tokens: u32,
+    #[serde(default)]
+    pub output_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnthropicResponse {
+    pub content: Vec<AnthropicContent>,
+    pub usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnthropicStreamMessage {
+    pub usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnthropicDelta {
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnthropicStreamEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub delta: Option<AnthropicDelta>,
+    // Input tokens arrive on message_start, output tokens on message_delta
+    pub message: Option<AnthropicStreamMessage>,
+    pub usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OllamaOptions {
+    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OllamaChat {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub stream: bool,
+    pub options: OllamaOptions,
+    // "json" or a JSON schema the reply must follow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaResponse {
+    pub message: APIMessage,
+    #[serde(default)]
+    pub prompt_eval_count: u32,
+    #[serde(default)]
+    pub eval_count: u32,
 }